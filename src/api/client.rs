@@ -1,46 +1,187 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 use image::DynamicImage;
 use image::GenericImageView;
 use image::ImageBuffer;
 use image::RgbaImage;
 
+use futures::{stream, StreamExt};
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache};
 use reqwest::Client;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use tokio::sync::Semaphore;
 
-use serde::{Deserialize, Serialize};
-
+use crate::progress::{Progress, ProgressHook};
 use crate::shapes::point::Point;
 use crate::shapes::rect::Rect;
 
 use super::models::MapData;
 use super::models::AreaData;
+use super::models::Marker;
 use super::models::MarkerData;
+use super::models::PointDetail;
 use super::models::RegionData;
 
+/// how the HTTP cache is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// fresh responses come from the cache, stale ones are revalidated.
+    #[default]
+    Default,
+    /// only the cache is used, requests that were never cached fail.
+    OfflineOnly,
+    /// everything is fetched again, and the cache is updated.
+    Refresh,
+    /// everything is fetched, and nothing is cached.
+    NoStore,
+}
+
+impl CachePolicy {
+    /// `default`, `offline`, `refresh` or `no-store`.
+    pub fn parse(policy: &str) -> Option<Self> {
+        match policy {
+            "default" => Some(Self::Default),
+            "offline" | "offline-only" => Some(Self::OfflineOnly),
+            "refresh" => Some(Self::Refresh),
+            "no-store" => Some(Self::NoStore),
+            _ => None,
+        }
+    }
+
+    fn mode(&self) -> CacheMode {
+        match self {
+            Self::Default => CacheMode::Default,
+            Self::OfflineOnly => CacheMode::OnlyIfCached,
+            Self::Refresh => CacheMode::Reload,
+            Self::NoStore => CacheMode::NoStore,
+        }
+    }
+}
+
+/// builds an `ApiClient`, see `ApiClient::builder`.
+pub struct ApiClientBuilder {
+    client: Option<Client>,
+    cache_dir: PathBuf,
+    cache_policy: CachePolicy,
+    max_concurrent_requests: usize,
+    progress: Option<ProgressHook>,
+}
+
+impl Default for ApiClientBuilder {
+    fn default() -> Self {
+        Self {
+            client: None,
+            cache_dir: PathBuf::from("./http-cacache"),
+            cache_policy: CachePolicy::Default,
+            max_concurrent_requests: 8,
+            progress: None,
+        }
+    }
+}
+
+impl ApiClientBuilder {
+    /// the reqwest client to send requests with, for proxies, timeouts, user agents etc.
+    /// the cache is added on top of it.
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// where responses are cached, `./http-cacache` by default.
+    pub fn cache_dir(mut self, cache_dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = cache_dir.into();
+        self
+    }
+
+    pub fn cache_policy(mut self, cache_policy: CachePolicy) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+
+    /// how many requests can be in flight at once, 8 by default.
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    /// called as map slices are fetched, see `Progress`
+    pub fn progress(mut self, progress: ProgressHook) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn build(self) -> ApiClient {
+        let client = ClientBuilder::new(self.client.unwrap_or_default())
+            .with(Cache(HttpCache {
+                mode: self.cache_policy.mode(),
+                manager: CACacheManager {
+                    path: self.cache_dir.to_string_lossy().into_owned(),
+                },
+                options: None,
+            }))
+            .build();
+
+        ApiClient {
+            client,
+            point_details: Mutex::new(HashMap::new()),
+            requests: Semaphore::new(self.max_concurrent_requests),
+            max_concurrent_requests: self.max_concurrent_requests,
+            progress: self.progress,
+        }
+    }
+}
+
 pub struct ApiClient {
     client: ClientWithMiddleware,
+    /// point details fetched so far by (map id, point id), there's one request per point.
+    point_details: Mutex<HashMap<(u8, u64), PointDetail>>,
+    /// a permit per request in flight.
+    requests: Semaphore,
+    max_concurrent_requests: usize,
+    progress: Option<ProgressHook>,
+}
+
+impl Default for ApiClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ApiClient {
     // returns a new instance of client.
     // internally this is a new http reqwest client with caching middleware.
     pub fn new() -> Self {
-        let client = ClientBuilder::new(Client::new())
-            .with(Cache(HttpCache {
-                mode: CacheMode::Default,
-                manager: CACacheManager::default(),
-                options: None,
-            }))
-            .build();
+        Self::builder().build()
+    }
 
-        Self { client }
+    /// for a client with another cache, cache policy or request limit.
+    pub fn builder() -> ApiClientBuilder {
+        ApiClientBuilder::default()
+    }
+
+    /// reports the event to the progress hook, if there's one.
+    pub fn report(&self, progress: Progress) {
+        if let Some(hook) = &self.progress {
+            hook(&progress);
+        }
+    }
+
+    async fn get_json(&self, url: &str) -> anyhow::Result<serde_json::Value> {
+        let _permit = self.requests.acquire().await?;
+        Ok(self.client.get(url).send().await?.json().await?)
+    }
+
+    async fn get_bytes(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        let _permit = self.requests.acquire().await?;
+        Ok(self.client.get(url).send().await?.bytes().await?.to_vec())
     }
 
     pub async fn fetch_map_ids(&self) -> anyhow::Result<Vec<u8>>{
         let url = "https://sg-public-api.hoyolab.com/common/map_user/ys_obc/v1/map/list?app_sn=ys_obc&lang=en-us";
-        let response: serde_json::Value = self.client.get(url).send().await?.json().await?;
+        let response: serde_json::Value = self.get_json(url).await?;
         // TODO: this breaks if api changes (shouldn't happen since versioning is used.)
         let data = &response["data"]["list"].as_array().unwrap();
         let data: Vec<u8> = data.iter().map(|val| val["id"].as_u64().unwrap() as u8).collect();
@@ -52,18 +193,18 @@ impl ApiClient {
     /// fetches the MapData for given map_id
     pub async fn fetch_map_data(&self, map_id: u8) -> anyhow::Result<MapData> {
         let url = format!("https://sg-public-api-static.hoyolab.com/common/map_user/ys_obc/v1/map/info?map_id={map_id}&app_sn=ys_obc&lang=en-us");
-        let response: serde_json::Value = self.client.get(url).send().await?.json().await?;
+        let response: serde_json::Value = self.get_json(&url).await?;
         // TODO: this breaks if api changes (shouldn't happen since versioning is used.)
         let data = &response["data"]["info"]["detail"].as_str().unwrap();
         // TODO: this might break if the structure changes (shouldn't happen snice
         // versoning is used.)
-        let map_data: MapData = serde_json::from_str(&data).unwrap();
+        let map_data: MapData = serde_json::from_str(data).unwrap();
         Ok(map_data)
     }
 
     /// fetches the image (map) for the given URL
     pub async fn fetch_image(&self, url: &str) -> anyhow::Result<DynamicImage> {
-        let bytes = self.client.get(url).send().await?.bytes().await?;
+        let bytes = self.get_bytes(url).await?;
      
         let reader = image::io::Reader::new(std::io::Cursor::new(bytes))
             .with_guessed_format()
@@ -78,10 +219,13 @@ impl ApiClient {
         // TODO: this is unncessary allocation if frame doesn't fit anywhere in map
         
         let mut output: RgbaImage =
-            ImageBuffer::new((frame.rx - frame.lx) as u32, (frame.ry - frame.ly) as u32);
+            ImageBuffer::new(frame.width(), frame.height());
 
         let mut map_chunk_dimensions: Option<(u32, u32)> = None;
 
+        // the slices overlapping the frame, with the rect of the slice and the common rect.
+        let mut wanted_chunks = vec![];
+
         // FROM HERE ONWARDS _r means the rect variant
         for (y, row) in (0..).zip(map_data.slices.iter()) {
             for (x, map_chunk) in (0..).zip(row.iter()) {
@@ -95,11 +239,13 @@ impl ApiClient {
                         (width, height)
                     }
                     None => {
-                        let Ok(map_chunk) =
-                            self.fetch_image(url).await else {
-                                println!("error: could not fetch image");
+                        let map_chunk = match self.fetch_image(url).await {
+                            Ok(map_chunk) => map_chunk,
+                            Err(e) => {
+                                tracing::warn!(url, error = %e, "could not fetch the map slice");
                                 return None;
-                            };
+                            }
+                        };
                         map_chunk_dimensions = Some(map_chunk.dimensions());
                         map_chunk.dimensions()
                         
@@ -111,51 +257,69 @@ impl ApiClient {
                     Rect::new(x * width, y * height, (x + 1) * width, (y + 1) * height);
                 
 
-                println!("map chunk: {:?}", map_chunk_r);
-                println!("frame: {:?}", frame);
-
                 // the common rect between map chunk and given frame
-                let Some(extracted_chunk_r) = map_chunk_r.common(&frame) else {
-                    println!("nothing common");
+                let Some(extracted_chunk_r) = map_chunk_r.common(frame) else {
+                    tracing::trace!(?map_chunk_r, ?frame, "slice is outside the frame");
                     continue;
                 };
 
-                let Ok(map_chunk) =
-                self.fetch_image(url).await else {
-                    println!("error: could not fetch image");
-                    return None;
-                };
-                
-                println!("common: {:?}", extracted_chunk_r);
-
-                // offset from map chunk
-                let extracted_chunk_mc_r = extracted_chunk_r
-                    .translate_axes(Point::new(map_chunk_r.lx as f32, map_chunk_r.ly as f32));
-
-                // offset from frame.
-                let output_chunk_f_r =
-                    extracted_chunk_r.translate_axes(Point::new(frame.lx as f32, frame.ly as f32));
-
-                // TODO: .to_image() seems expensive.
-                let extracted_chunk = map_chunk
-                    .view(
-                        extracted_chunk_mc_r.lx,
-                        extracted_chunk_mc_r.ly,
-                        extracted_chunk_mc_r.width(),
-                        extracted_chunk_mc_r.height(),
-                    )
-                    .to_image();
-
-                image::imageops::replace(
-                    &mut output,
-                    &extracted_chunk,
-                    output_chunk_f_r.lx.into(),
-                    output_chunk_f_r.ly.into(),
-                );
+                tracing::trace!(?map_chunk_r, common = ?extracted_chunk_r, "slice overlaps the frame");
+
+                wanted_chunks.push((url.clone(), map_chunk_r, extracted_chunk_r));
             }
         }
 
-   
+        // fetch the slices concurrently, and put each one in as soon as it's there so that
+        // only a few are in memory at once.
+        let total = wanted_chunks.len();
+        tracing::debug!(slices = total, ?frame, "fetching the map chunk");
+        let done = AtomicUsize::new(0);
+        let mut fetched_chunks = stream::iter(wanted_chunks)
+            .map(|(url, map_chunk_r, extracted_chunk_r)| {
+                let done = &done;
+                async move {
+                    let map_chunk = self.fetch_image(&url).await;
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    self.report(Progress::TileFetched { done, total });
+                    (map_chunk, map_chunk_r, extracted_chunk_r)
+                }
+            })
+            .buffer_unordered(self.max_concurrent_requests);
+
+        while let Some((map_chunk, map_chunk_r, extracted_chunk_r)) = fetched_chunks.next().await {
+            let map_chunk = match map_chunk {
+                Ok(map_chunk) => map_chunk,
+                Err(e) => {
+                    tracing::warn!(error = %e, "could not fetch the map slice");
+                    return None;
+                }
+            };
+
+            // offset from map chunk
+            let extracted_chunk_mc_r = extracted_chunk_r
+                .translate_axes(Point::new(map_chunk_r.lx as f32, map_chunk_r.ly as f32));
+
+            // offset from frame.
+            let output_chunk_f_r =
+                extracted_chunk_r.translate_axes(Point::new(frame.lx as f32, frame.ly as f32));
+
+            // TODO: .to_image() seems expensive.
+            let extracted_chunk = map_chunk
+                .view(
+                    extracted_chunk_mc_r.lx,
+                    extracted_chunk_mc_r.ly,
+                    extracted_chunk_mc_r.width(),
+                    extracted_chunk_mc_r.height(),
+                )
+                .to_image();
+
+            image::imageops::replace(
+                &mut output,
+                &extracted_chunk,
+                output_chunk_f_r.lx.into(),
+                output_chunk_f_r.ly.into(),
+            );
+        }
 
         // TODO: IF NO matches found. then white image
         // if if frame is partially outside of map. then partial image
@@ -165,7 +329,7 @@ impl ApiClient {
 
     pub async fn fetch_areas(&self) -> anyhow::Result<Vec<AreaData>> {
         let url = "https://sg-public-api-static.hoyolab.com/common/map_user/ys_obc/v1/map/get_area_pageLabel?map_id=9&app_sn=ys_obc&lang=en-us";
-        let mut response: serde_json::Value = self.client.get(url).send().await?.json().await?;
+        let mut response: serde_json::Value = self.get_json(url).await?;
 
         let areas: Vec<AreaData> = serde_json::from_value(response["data"]["list"].take())?;
 
@@ -175,7 +339,7 @@ impl ApiClient {
     pub async fn fetch_regions(&self, map_id: u8) -> anyhow::Result<Vec<RegionData>> {
         let url = format!("https://sg-public-api-static.hoyolab.com/common/map_user/ys_obc/v1/map/map_anchor/list?map_id={map_id}&app_sn=ys_obc&lang=en-us");
 
-        let mut response: serde_json::Value = self.client.get(url).send().await?.json().await?;
+        let mut response: serde_json::Value = self.get_json(&url).await?;
 
         let regions: Vec<RegionData> = serde_json::from_value(response["data"]["list"].take())?;
 
@@ -186,10 +350,61 @@ impl ApiClient {
     pub async fn fetch_marker_data(&self, map_id: u8) -> anyhow::Result<MarkerData> {
         let url = format!("https://sg-public-api-static.hoyolab.com/common/map_user/ys_obc/v1/map/point/list?map_id={map_id}&app_sn=ys_obc&lang=en-us");
 
-        let mut response:  serde_json::Value = self.client.get(url).send().await?.json().await?;
-        let  mut marker_data: MarkerData = serde_json::from_value(response["data"].take())?;
+        let mut response:  serde_json::Value = self.get_json(&url).await?;
+        let marker_data: MarkerData = serde_json::from_value(response["data"].take())?;
         Ok(marker_data)
     }
+
+    /// fetches the details (hint text, screenshot) of the point with the given marker id.
+    /// details are cached for the lifetime of the client.
+    pub async fn fetch_point_detail(&self, map_id: u8, point_id: u64) -> anyhow::Result<PointDetail> {
+        if let Some(detail) = self.point_details.lock().unwrap().get(&(map_id, point_id)) {
+            return Ok(detail.clone());
+        }
+
+        let url = format!("https://sg-public-api-static.hoyolab.com/common/map_user/ys_obc/v1/map/point/info?map_id={map_id}&point_id={point_id}&app_sn=ys_obc&lang=en-us");
+
+        let mut response: serde_json::Value = self.get_json(&url).await?;
+        let detail: PointDetail = serde_json::from_value(response["data"]["info"].take())?;
+
+        self.point_details
+            .lock()
+            .unwrap()
+            .insert((map_id, point_id), detail.clone());
+        Ok(detail)
+    }
+
+    /// fetches the details of every marker that has an id, `max_concurrent_requests` at a
+    /// time, and fills them in. markers without ids are left as they are, and so are the
+    /// ones whose details couldn't be fetched (with a warning), so that a bad point doesn't
+    /// fail a whole export.
+    pub async fn fetch_point_details<'a>(
+        &self,
+        map_id: u8,
+        markers: impl IntoIterator<Item = &'a mut Marker>,
+    ) {
+        let with_ids = markers
+            .into_iter()
+            .filter_map(|marker| Some((marker.id?, marker)));
+        let mut details = stream::iter(with_ids)
+            .map(|(point_id, marker)| async move {
+                let detail = self.fetch_point_detail(map_id, point_id).await;
+                (point_id, marker, detail)
+            })
+            .buffer_unordered(self.max_concurrent_requests);
+
+        while let Some((point_id, marker, detail)) = details.next().await {
+            match detail {
+                Ok(detail) => marker.apply_detail(&detail),
+                Err(e) => tracing::warn!(
+                    map_id,
+                    point_id,
+                    error = %e,
+                    "could not fetch the point detail"
+                ),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -218,6 +433,14 @@ mod test {
 
 
 
+    #[test]
+    fn test_cache_policy() {
+        assert_eq!(CachePolicy::parse("offline"), Some(CachePolicy::OfflineOnly));
+        assert_eq!(CachePolicy::parse("no-store"), Some(CachePolicy::NoStore));
+        assert_eq!(CachePolicy::parse("sometimes"), None);
+        assert_eq!(CachePolicy::Refresh.mode(), CacheMode::Reload);
+    }
+
     #[test]
     fn test_get_map_chunk() {
         let client = ApiClient::new();
@@ -283,7 +506,7 @@ mod test {
 
         rt.unwrap().block_on(async {
             match client.fetch_marker_data(2).await {
-                Ok(marker_data) => {
+                Ok(_marker_data) => {
                     println!("success");
                 }
                 Err(e) => {
@@ -292,4 +515,31 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_fetch_point_detail() {
+        let client = ApiClient::new();
+        let rt = tokio::runtime::Runtime::new();
+
+        rt.unwrap().block_on(async {
+            let marker_data = match client.fetch_marker_data(2).await {
+                Ok(marker_data) => marker_data,
+                Err(e) => {
+                    println!("error occured: {:?}", e);
+                    return;
+                }
+            };
+            let Some(point_id) = marker_data.markers.iter().find_map(|marker| marker.id) else {
+                return;
+            };
+            match client.fetch_point_detail(2, point_id).await {
+                Ok(detail) => {
+                    println!("{:#?}", detail);
+                }
+                Err(e) => {
+                    println!("error occured: {:?}", e);
+                }
+            }
+        });
+    }
 }
@@ -26,6 +26,10 @@ impl MapData {
             y: self.padding.1,
         }
     }
+
+    pub fn total_size(&self) -> (i32, i32) {
+        self.total_size
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,19 +38,19 @@ pub struct AreaData {
     #[serde(rename = "pc_icon_url")]
     pub icon_url: String,
     #[serde(rename = "l_x")]
-    lx: f32,
+    pub lx: f32,
     #[serde(rename = "l_y")]
-    ly: f32,
+    pub ly: f32,
     #[serde(rename = "r_x")]
-    rx: f32,
+    pub rx: f32,
     #[serde(rename = "r_y")]
-    ry: f32,
+    pub ry: f32,
     pub map_id: u8,
 }
 
 impl AreaData {
     /// converts the relative frame from origin to absolute (from top left of the map)
-    fn get_abs_frame(&self, old_origin: Point) -> Rect {
+    pub fn get_abs_frame(&self, old_origin: Point) -> Rect {
         // origin = (h,k)
         // new_origin = (-h,-k); basically shifting back the origin to 0, 0 (top left of the map)
         // (X,Y) = (x - (-h), y - (-k)); new coorinates with respect to new origin
@@ -92,6 +96,11 @@ impl RegionData {
         self.map_id.parse().unwrap()
     }
 
+    /// whether the point (relative to the origin, like marker positions) is inside the frame.
+    pub fn contains(&self, pos: &Point) -> bool {
+        pos.x >= self.lx && pos.x < self.rx && pos.y >= self.ly && pos.y < self.ry
+    }
+
     /// returns the frame after translating origin to top left of the map.
     pub fn get_abs_frame(&self, old_origin: &Point) -> Rect {
         // origin = (h,k)
@@ -119,25 +128,173 @@ pub struct MarkerData {
     
 }
 
+impl MarkerData {
+    /// labels whose name contains any of the desired labels.
+    pub fn matching_labels(&self, desired_marker_labels: &[String]) -> Vec<&Label> {
+        self.labels
+            .iter()
+            .filter(|label| {
+                desired_marker_labels
+                    .iter()
+                    .any(|desired_label| label.name.contains(desired_label))
+            })
+            .collect()
+    }
+}
+
+/// a point on the map. the fields past the position are optional, so that a point the API
+/// sends differently (or without them) doesn't fail the whole list. fields not modeled
+/// here are kept in `extra`, so that saved snapshots keep the whole payload.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Marker {
+    /// id of the point.
+    #[serde(default)]
+    pub id: Option<u64>,
     pub label_id: i32,
     pub area_id: u8,
     #[serde(rename = "x_pos")]
     x: f32,
     #[serde(rename = "y_pos")]
     y: f32,
+    /// description of the point, usually with hints on how to get there. can be HTML.
+    #[serde(default)]
+    pub content: Option<String>,
+    /// url of a screenshot of the point.
+    #[serde(default, rename = "img")]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub author_name: Option<String>,
+    /// creation time, as sent by the API ("yyyy-mm-dd hh:mm:ss").
+    #[serde(default)]
+    pub ctime: Option<String>,
+    #[serde(default)]
+    pub display_state: Option<i32>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl Marker {
     pub fn pos(&self) -> Point {
         Point::new(self.x, self.y)
     }
+
+    /// the description, empty if there's none.
+    pub fn content(&self) -> &str {
+        self.content.as_deref().unwrap_or_default()
+    }
+
+    /// the screenshot url, empty if there's none.
+    pub fn image(&self) -> &str {
+        self.image.as_deref().unwrap_or_default()
+    }
+
+    /// fills in the content and image from the details, keeping the ones already known.
+    pub fn apply_detail(&mut self, detail: &PointDetail) {
+        if self.content().is_empty() && !detail.content.is_empty() {
+            self.content = Some(detail.content.clone());
+        }
+        if self.image().is_empty() && !detail.image.is_empty() {
+            self.image = Some(detail.image.clone());
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// details of a single point, that the point list doesn't have: the hint text and the
+/// reference screenshot shown when clicking a marker on the official map.
+/// fields not modeled here are kept in `extra`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct PointDetail {
+    pub id: u64,
+    #[serde(default)]
+    pub label_id: i32,
+    /// can be HTML.
+    #[serde(default)]
+    pub content: String,
+    #[serde(default, rename = "img")]
+    pub image: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// a kind of marker. labels form a tree (categories -> items) through `parent_id`,
+/// fields not modeled here are kept in `extra`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Label {
     pub name: String,
     pub icon: String,
     pub id: i32,
+    /// id of the category label, `0` for top level labels.
+    #[serde(default)]
+    pub parent_id: i32,
+    /// level in the tree, starting at 1 for categories.
+    #[serde(default)]
+    pub depth: i32,
+    #[serde(default)]
+    pub node_type: i32,
+    /// labels with a higher priority are shown first in the official map.
+    #[serde(default)]
+    pub display_priority: i32,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_marker_optional_fields() {
+        let json = r#"[{"id": 42, "label_id": 1, "area_id": 3, "x_pos": 5.0, "y_pos": 6.0,
+                        "content": "under the bridge", "ctime": null, "point_group_id": 2},
+                       {"label_id": 1, "area_id": 3, "x_pos": 7.0, "y_pos": 8.0}]"#;
+        let markers: Vec<Marker> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(markers[0].id, Some(42));
+        assert_eq!(markers[0].content(), "under the bridge");
+        assert_eq!(markers[0].ctime, None);
+        assert_eq!(markers[1].id, None);
+        assert_eq!(markers[1].content(), "");
+        assert!(markers[1].extra.is_empty());
+
+        // labels keep what isn't modeled.
+        let label: Label = serde_json::from_str(
+            r#"{"name": "Chest", "icon": "chest.png", "id": 5, "is_hidden": false}"#,
+        )
+        .unwrap();
+        assert_eq!(label.extra["is_hidden"], false);
+    }
+
+    #[test]
+    fn test_marker_keeps_unknown_fields() {
+        let json = r#"{"id": 42, "label_id": 1, "area_id": 3, "x_pos": 5.0, "y_pos": 6.0,
+                       "content": "under the bridge", "point_group_id": 2}"#;
+        let marker: Marker = serde_json::from_str(json).unwrap();
+        assert_eq!(marker.extra["point_group_id"], 2);
+
+        let round_trip = serde_json::to_value(&marker).unwrap();
+        assert_eq!(round_trip["point_group_id"], 2);
+        assert_eq!(round_trip["x_pos"], 5.0);
+        let marker: Marker = serde_json::from_value(round_trip).unwrap();
+        assert_eq!(marker.extra["point_group_id"], 2);
+        assert_eq!(marker.content(), "under the bridge");
+
+        let detail: PointDetail =
+            serde_json::from_str(r#"{"id": 42, "point_group_id": 2}"#).unwrap();
+        assert_eq!(detail.extra["point_group_id"], 2);
+    }
+
+    #[test]
+    fn test_apply_detail() {
+        let mut marker: Marker =
+            serde_json::from_str(r#"{"id": 42, "label_id": 1, "area_id": 3, "x_pos": 5.0, "y_pos": 6.0}"#)
+                .unwrap();
+        let detail: PointDetail = serde_json::from_str(
+            r#"{"id": 42, "content": "behind the waterfall", "img": "https://example.com/42.png"}"#,
+        )
+        .unwrap();
+
+        marker.apply_detail(&detail);
+        assert_eq!(marker.content(), "behind the waterfall");
+        assert_eq!(marker.image(), "https://example.com/42.png");
+    }
 }
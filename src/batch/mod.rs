@@ -0,0 +1,433 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+
+use crate::export::raster::{self, RasterFormat, RasterMetadata};
+use crate::render::RenderOptions;
+use crate::selection::MapSelection;
+use crate::shapes::point::Point;
+use crate::MapGenerator;
+
+/// a list of renders, run together by `run_jobs`. read with `load_job_file`.
+///
+/// ```toml
+/// concurrency = 4
+///
+/// [[job]]
+/// region = "Vimara Village"
+/// labels = ["Padisarah", "Teleport Waypoint"]
+/// output = "guides/padisarah.png"
+/// style.annotations = {}
+///
+/// [[job]]
+/// bbox = { map = 2, top_left = [-1000, 200], bottom_right = [0, 1200] }
+/// labels = ["Sumeru Rose"]
+/// output = "guides/roses.html"
+///
+/// [[job]]
+/// area = "Inazuma"
+/// labels = ["Electroculus"]
+/// output = "guides/electroculi.jpg"
+/// format = "jpeg:80"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobFile {
+    /// how many jobs run at the same time.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(rename = "job", default)]
+    pub jobs: Vec<Job>,
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+/// what to render: set one of `region`, `area` or `bbox`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// shown in the summary, the output path if not set.
+    pub name: Option<String>,
+    /// a region or sub region, see `gen_region_map`.
+    pub region: Option<String>,
+    /// an area (nation), see `gen_area_map`.
+    pub area: Option<String>,
+    pub bbox: Option<BoundingBox>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(default)]
+    pub style: RenderOptions,
+    /// where the output goes, relative to the job file. the format is picked by the
+    /// extension (png, jpg/jpeg, svg or html) unless `format` is set.
+    pub output: PathBuf,
+    /// how to encode an image output, like `jpeg:80`. see `RasterFormat`
+    pub format: Option<RasterFormat>,
+    /// leave the region, labels and data timestamp out of PNGs.
+    #[serde(default)]
+    pub no_metadata: bool,
+}
+
+/// a box on a map, in the API's co-ordinates (relative to the map's origin).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub map: u8,
+    pub top_left: (f32, f32),
+    pub bottom_right: (f32, f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Raster(RasterFormat),
+    Svg,
+    Html,
+}
+
+impl Job {
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.output.display().to_string())
+    }
+
+    fn format(&self) -> anyhow::Result<OutputFormat> {
+        let extension = self
+            .output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if let Some(format) = self.format {
+            // `format` only picks how images are encoded, documents can't be.
+            anyhow::ensure!(
+                !matches!(extension.as_str(), "svg" | "html"),
+                "format is for image outputs, not {extension}"
+            );
+            return Ok(OutputFormat::Raster(format));
+        }
+        match extension.as_str() {
+            "png" | "jpg" | "jpeg" | "webp" => RasterFormat::parse(&extension)
+                .map(OutputFormat::Raster)
+                .ok_or_else(|| anyhow::anyhow!("{extension} output needs the webp feature")),
+            "avif" => anyhow::bail!(raster::AVIF_UNSUPPORTED),
+            "svg" => Ok(OutputFormat::Svg),
+            "html" => Ok(OutputFormat::Html),
+            _ => anyhow::bail!(
+                "unknown output format '{}', use png, jpg, svg or html",
+                extension
+            ),
+        }
+    }
+
+    /// checks the job before anything is fetched, so that mistakes fail fast.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let targets = [
+            self.region.is_some(),
+            self.area.is_some(),
+            self.bbox.is_some(),
+        ];
+        anyhow::ensure!(
+            targets.iter().filter(|set| **set).count() == 1,
+            "set exactly one of region, area or bbox"
+        );
+        self.format()?;
+        Ok(())
+    }
+}
+
+/// reads a job file, TOML or JSON by its extension. YAML isn't supported.
+/// outputs are made relative to the directory of the job file.
+pub fn load_job_file(path: impl AsRef<Path>) -> anyhow::Result<JobFile> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let json = match extension.as_str() {
+        "toml" => false,
+        "json" => true,
+        "yaml" | "yml" => anyhow::bail!("YAML job files aren't supported, use TOML or JSON"),
+        _ => anyhow::bail!(
+            "unknown job file extension '{}', use .toml or .json",
+            extension
+        ),
+    };
+    let text = fs::read_to_string(path)?;
+    let mut job_file: JobFile = if json {
+        serde_json::from_str(&text)?
+    } else {
+        toml::from_str(&text)?
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    for job in &mut job_file.jobs {
+        job.output = base.join(&job.output);
+    }
+    Ok(job_file)
+}
+
+/// how a job went.
+#[derive(Debug)]
+pub struct JobResult {
+    pub name: String,
+    pub output: PathBuf,
+    pub result: anyhow::Result<()>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    /// in the order of the job file.
+    pub results: Vec<JobResult>,
+}
+
+impl BatchSummary {
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|job| job.result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+
+    /// a line per job and the counts at the end.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for job in &self.results {
+            let _ = match &job.result {
+                Ok(()) => writeln!(
+                    text,
+                    "ok     {} -> {} ({:.1}s)",
+                    job.name,
+                    job.output.display(),
+                    job.duration.as_secs_f32()
+                ),
+                Err(e) => writeln!(text, "failed {}: {}", job.name, e),
+            };
+        }
+        let _ = writeln!(
+            text,
+            "{} succeeded, {} failed",
+            self.succeeded(),
+            self.failed()
+        );
+        text
+    }
+}
+
+/// runs the jobs, `concurrency` at a time, with generators sharing `generator`'s client
+/// so that the maps, markers and icons are fetched once for all of them.
+/// failing jobs don't stop the others, see the summary for how each one went.
+pub async fn run_jobs(generator: &MapGenerator, job_file: JobFile) -> BatchSummary {
+    let semaphore = Arc::new(Semaphore::new(job_file.concurrency.max(1)));
+
+    let mut handles = vec![];
+    for job in job_file.jobs {
+        let generator = generator.restyled(job.style.clone());
+        let semaphore = semaphore.clone();
+        handles.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let start = Instant::now();
+            let result = run_job(&generator, &job).await;
+            JobResult {
+                name: job.name(),
+                output: job.output,
+                result,
+                duration: start.elapsed(),
+            }
+        }));
+    }
+
+    let mut summary = BatchSummary::default();
+    for handle in handles {
+        match handle.await {
+            Ok(result) => summary.results.push(result),
+            Err(e) => summary.results.push(JobResult {
+                name: "?".to_string(),
+                output: PathBuf::new(),
+                result: Err(anyhow::anyhow!("job panicked: {e}")),
+                duration: Duration::ZERO,
+            }),
+        }
+    }
+    summary
+}
+
+async fn run_job(generator: &MapGenerator, job: &Job) -> anyhow::Result<()> {
+    job.validate()?;
+    let format = job.format()?;
+
+    let selection = if let Some(region) = &job.region {
+        generator.select_region(region).await?
+    } else if let Some(area) = &job.area {
+        generator.select_area(area).await?
+    } else if let Some(bbox) = &job.bbox {
+        let top_left = Point::new(bbox.top_left.0, bbox.top_left.1);
+        let bottom_right = Point::new(bbox.bottom_right.0, bbox.bottom_right.1);
+        Some(
+            generator
+                .select_bbox(bbox.map, top_left, bottom_right)
+                .await?,
+        )
+    } else {
+        None
+    };
+    let Some(selection) = selection else {
+        anyhow::bail!("nothing matched");
+    };
+
+    if let Some(parent) = job.output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // drawing and encoding are CPU heavy, so they run on a blocking thread instead of the
+    // async workers, where they'd hold up the downloads of the other jobs.
+    let (generator, job) = (generator.clone(), job.clone());
+    tokio::task::spawn_blocking(move || {
+        Handle::current().block_on(write_output(&generator, &job, format, selection))
+    })
+    .await?
+}
+
+async fn write_output(
+    generator: &MapGenerator,
+    job: &Job,
+    format: OutputFormat,
+    mut selection: MapSelection,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Raster(format) => {
+            let map = generator.render(&selection, &job.labels).await?;
+            let metadata = RasterMetadata::for_selection(&selection, &job.labels);
+            let metadata = (!job.no_metadata).then_some(&metadata);
+            raster::save(&map, format, metadata, &job.output)?;
+        }
+        OutputFormat::Svg | OutputFormat::Html => {
+            if job.style.point_details {
+                generator
+                    .fetch_point_details(&mut selection, &job.labels)
+                    .await;
+            }
+            let document = match format {
+                OutputFormat::Svg => generator.render_svg(&selection, &job.labels).await?,
+                _ => generator.render_html(&selection, &job.labels).await?,
+            };
+            fs::write(&job.output, document)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn job(toml: &str) -> Job {
+        let mut job_file: JobFile = toml::from_str(toml).unwrap();
+        job_file.jobs.remove(0)
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = job("[[job]]\nregion = \"Mondstadt\"\noutput = \"a.PNG\"");
+        assert!(valid.validate().is_ok());
+        assert_eq!(
+            valid.format().unwrap(),
+            OutputFormat::Raster(RasterFormat::Png)
+        );
+
+        let explicit = job("[[job]]\narea = \"Liyue\"\noutput = \"a.img\"\nformat = \"jpeg:70\"");
+        assert_eq!(
+            explicit.format().unwrap(),
+            OutputFormat::Raster(RasterFormat::Jpeg { quality: 70 })
+        );
+
+        let two_targets = job("[[job]]\nregion = \"A\"\narea = \"B\"\noutput = \"a.png\"");
+        assert!(two_targets.validate().is_err());
+
+        let bad_format = job("[[job]]\narea = \"Liyue\"\noutput = \"a.gif\"");
+        assert!(bad_format.validate().is_err());
+
+        let encoded_svg = job("[[job]]\narea = \"Liyue\"\noutput = \"a.svg\"\nformat = \"png\"");
+        assert!(encoded_svg.validate().is_err());
+    }
+
+    #[test]
+    fn test_job_file() {
+        let job_file: JobFile = toml::from_str(
+            "[[job]]\n\
+             bbox = { map = 2, top_left = [0, 0], bottom_right = [100, 100] }\n\
+             output = \"box.html\"\n\
+             style.point_details = true\n\
+             \n\
+             [[job]]\n\
+             area = \"Sumeru\"\n\
+             output = \"sumeru.png\"\n\
+             [job.style.annotations]\n\
+             north_arrow = false",
+        )
+        .unwrap();
+
+        assert_eq!(job_file.concurrency, 4);
+        assert_eq!(
+            job_file.jobs[0].bbox.as_ref().unwrap().bottom_right,
+            (100.0, 100.0)
+        );
+        assert!(job_file.jobs[0].style.point_details);
+        // the table header belongs to the last job.
+        assert!(
+            !job_file.jobs[1]
+                .style
+                .annotations
+                .as_ref()
+                .unwrap()
+                .north_arrow
+        );
+
+        // a table defined twice is an error.
+        assert!(toml::from_str::<JobFile>(
+            "[[job]]\narea = \"Liyue\"\noutput = \"a.png\"\n\
+             [job.style]\nhd = true\n[job.style]\nhd = false"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_load_job_file_extension() {
+        let error = load_job_file("guides.yaml").unwrap_err();
+        assert!(error.to_string().contains("YAML"));
+        assert!(load_job_file("guides.txt").is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = BatchSummary {
+            results: vec![
+                JobResult {
+                    name: "oculi".to_string(),
+                    output: PathBuf::from("oculi.png"),
+                    result: Ok(()),
+                    duration: Duration::from_millis(1500),
+                },
+                JobResult {
+                    name: "roses".to_string(),
+                    output: PathBuf::from("roses.png"),
+                    result: Err(anyhow::anyhow!("nothing matched")),
+                    duration: Duration::ZERO,
+                },
+            ],
+        };
+
+        assert_eq!(
+            summary.to_text(),
+            "ok     oculi -> oculi.png (1.5s)\n\
+             failed roses: nothing matched\n\
+             1 succeeded, 1 failed\n"
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use genshin_map_generator::{batch, MapGenerator};
+
+const USAGE: &str = "usage: batch <jobs.toml | jobs.json>";
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!(USAGE))?;
+    let job_file = batch::load_job_file(&path)?;
+    for job in &job_file.jobs {
+        job.validate()
+            .map_err(|e| anyhow::anyhow!("job {}: {e}", job.name()))?;
+    }
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let summary = rt.block_on(batch::run_jobs(&MapGenerator::new(), job_file));
+
+    print!("{}", summary.to_text());
+    if summary.failed() > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
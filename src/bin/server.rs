@@ -0,0 +1,31 @@
+use std::net::SocketAddr;
+
+use genshin_map_generator::progress::ProgressEvents;
+use genshin_map_generator::{server, MapGenerator};
+
+const USAGE: &str = "usage: server [--addr 127.0.0.1:8080] [--tiles-dir tiles]";
+
+fn main() -> anyhow::Result<()> {
+    let mut addr: SocketAddr = "127.0.0.1:8080".parse()?;
+    let mut tiles_dir = String::from("tiles");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{arg} needs a value\n{USAGE}"))?;
+        match arg.as_str() {
+            "--addr" => addr = value.parse()?,
+            "--tiles-dir" => tiles_dir = value,
+            _ => anyhow::bail!("unknown argument {arg}\n{USAGE}"),
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        println!("listening on http://{addr}");
+        let events = ProgressEvents::new();
+        let generator = MapGenerator::builder().progress(events.hook()).build();
+        server::serve(generator, addr, tiles_dir, events).await
+    })
+}
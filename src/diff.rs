@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::models::{Label, Marker, MarkerData};
+use crate::shapes::point::Point;
+use crate::spatial::MarkerIndex;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// markers of the same label closer than this (in map units) are the same marker.
+    pub same_distance: f32,
+    /// markers of the same label closer than this are the same marker, moved.
+    /// further apart, the old one is removed and the new one added.
+    pub move_distance: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            same_distance: 1.0,
+            move_distance: 50.0,
+        }
+    }
+}
+
+/// a marker that is only in one of the snapshots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffMarker {
+    /// id of the point, if the snapshot had ids.
+    pub id: Option<u64>,
+    pub label: String,
+    pub label_id: i32,
+    /// position relative to the origin, same as the API.
+    pub pos: Point,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovedMarker {
+    pub id: Option<u64>,
+    pub label: String,
+    pub label_id: i32,
+    pub from: Point,
+    pub to: Point,
+}
+
+/// what changed between an old and a new snapshot of the markers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarkerDiff {
+    pub added: Vec<DiffMarker>,
+    pub removed: Vec<DiffMarker>,
+    pub moved: Vec<MovedMarker>,
+    pub unchanged: usize,
+}
+
+impl MarkerDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+
+    /// changes grouped by label, "+" for added, "-" for removed and "~" for moved markers.
+    pub fn to_text(&self) -> String {
+        let mut labels: Vec<(i32, &str)> = vec![];
+        let changed_labels = self
+            .added
+            .iter()
+            .chain(self.removed.iter())
+            .map(|marker| (marker.label_id, marker.label.as_str()))
+            .chain(
+                self.moved
+                    .iter()
+                    .map(|marker| (marker.label_id, marker.label.as_str())),
+            );
+        for (id, name) in changed_labels {
+            if !labels.iter().any(|(label_id, _)| *label_id == id) {
+                labels.push((id, name));
+            }
+        }
+
+        let mut text = String::new();
+        for (id, name) in labels {
+            let added: Vec<_> = self.added.iter().filter(|m| m.label_id == id).collect();
+            let removed: Vec<_> = self.removed.iter().filter(|m| m.label_id == id).collect();
+            let moved: Vec<_> = self.moved.iter().filter(|m| m.label_id == id).collect();
+
+            let _ = writeln!(
+                text,
+                "{}: {} added, {} removed, {} moved",
+                name,
+                added.len(),
+                removed.len(),
+                moved.len()
+            );
+            for marker in added {
+                let _ = writeln!(text, "  + ({:.0}, {:.0})", marker.pos.x, marker.pos.y);
+            }
+            for marker in removed {
+                let _ = writeln!(text, "  - ({:.0}, {:.0})", marker.pos.x, marker.pos.y);
+            }
+            for marker in moved {
+                let _ = writeln!(
+                    text,
+                    "  ~ ({:.0}, {:.0}) -> ({:.0}, {:.0})",
+                    marker.from.x, marker.from.y, marker.to.x, marker.to.y
+                );
+            }
+        }
+        let _ = writeln!(
+            text,
+            "total: {} added, {} removed, {} moved, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.moved.len(),
+            self.unchanged
+        );
+        text
+    }
+}
+
+/// positions of the markers of each label, by label id.
+fn group_by_label(markers: &[Marker]) -> HashMap<i32, Vec<usize>> {
+    let mut groups: HashMap<i32, Vec<usize>> = HashMap::new();
+    for (i, marker) in markers.iter().enumerate() {
+        groups.entry(marker.label_id).or_default().push(i);
+    }
+    groups
+}
+
+/// compares two snapshots of the markers of a map, for the labels matching the desired
+/// labels in either of them (an empty string matches every label).
+///
+/// markers of the same label are matched by id when both have one. markers without ids
+/// (like snapshots saved before ids were modeled) are matched by proximity instead: the
+/// closest old and new pairs are matched first, and pairs further apart than
+/// `move_distance` are counted as a removal and an addition.
+pub fn diff_markers(
+    old: &MarkerData,
+    new: &MarkerData,
+    desired_marker_labels: &[String],
+    options: &DiffOptions,
+) -> MarkerDiff {
+    diff_markers_where(old, new, desired_marker_labels, options, |_| true)
+}
+
+/// same as `diff_markers`, counting only the markers whose position (relative to the
+/// origin) `keep` accepts, unchanged ones included. moved markers count if either end is
+/// accepted.
+pub fn diff_markers_where(
+    old: &MarkerData,
+    new: &MarkerData,
+    desired_marker_labels: &[String],
+    options: &DiffOptions,
+    keep: impl Fn(Point) -> bool,
+) -> MarkerDiff {
+    let mut labels: Vec<&Label> = new.matching_labels(desired_marker_labels);
+    for label in old.matching_labels(desired_marker_labels) {
+        if !labels.iter().any(|other| other.id == label.id) {
+            labels.push(label);
+        }
+    }
+
+    let old_groups = group_by_label(&old.markers);
+    let new_groups = group_by_label(&new.markers);
+    let new_by_id: HashMap<u64, usize> = new
+        .markers
+        .iter()
+        .enumerate()
+        .filter_map(|(j, marker)| Some((marker.id?, j)))
+        .collect();
+    let new_index = MarkerIndex::new(new);
+    let mut old_matched = vec![false; old.markers.len()];
+    let mut new_matched = vec![false; new.markers.len()];
+    let mut diff = MarkerDiff::default();
+
+    for label in labels {
+        let old_group = old_groups.get(&label.id).map_or(&[][..], |g| &g[..]);
+        let new_group = new_groups.get(&label.id).map_or(&[][..], |g| &g[..]);
+
+        let diff_marker = |marker: &Marker| DiffMarker {
+            id: marker.id,
+            label: label.name.clone(),
+            label_id: label.id,
+            pos: marker.pos(),
+        };
+
+        // every old and new pair that could be the same marker: same id if both have one,
+        // else close enough. pairs with the same id go first, then the closest ones.
+        let mut pairs = vec![];
+        for &i in old_group {
+            let old_marker = &old.markers[i];
+            let by_id = old_marker
+                .id
+                .and_then(|id| new_by_id.get(&id))
+                .filter(|j| new.markers[**j].label_id == label.id);
+            if let Some(&j) = by_id {
+                pairs.push((
+                    false,
+                    old_marker.pos().distance(&new.markers[j].pos()),
+                    i,
+                    j,
+                ));
+            }
+            for j in new_index.positions_within_radius(
+                old_marker.pos(),
+                options.move_distance,
+                Some(label.id),
+            ) {
+                let new_marker = &new.markers[j];
+                if old_marker.id.is_none() || new_marker.id.is_none() {
+                    pairs.push((true, old_marker.pos().distance(&new_marker.pos()), i, j));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        for (_, distance, i, j) in pairs {
+            if old_matched[i] || new_matched[j] {
+                continue;
+            }
+            old_matched[i] = true;
+            new_matched[j] = true;
+            let (from, to) = (old.markers[i].pos(), new.markers[j].pos());
+            if distance <= options.same_distance {
+                if keep(to) {
+                    diff.unchanged += 1;
+                }
+            } else if keep(from) || keep(to) {
+                diff.moved.push(MovedMarker {
+                    id: new.markers[j].id,
+                    label: label.name.clone(),
+                    label_id: label.id,
+                    from,
+                    to,
+                });
+            }
+        }
+
+        diff.removed.extend(
+            old_group
+                .iter()
+                .filter(|i| !old_matched[**i])
+                .map(|i| &old.markers[*i])
+                .filter(|marker| keep(marker.pos()))
+                .map(diff_marker),
+        );
+        diff.added.extend(
+            new_group
+                .iter()
+                .filter(|j| !new_matched[**j])
+                .map(|j| &new.markers[*j])
+                .filter(|marker| keep(marker.pos()))
+                .map(diff_marker),
+        );
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn marker_data(points: &str) -> MarkerData {
+        serde_json::from_str(&format!(
+            r#"{{"point_list": [{}],
+               "label_list": [{{"name": "Sweet Flower", "icon": "", "id": 1}}]}}"#,
+            points
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_diff_markers() {
+        let old = marker_data(
+            r#"{"label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 100.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 500.0, "y_pos": 0.0}"#,
+        );
+        let new = marker_data(
+            r#"{"label_id": 1, "area_id": 1, "x_pos": 0.5, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 110.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 900.0, "y_pos": 0.0}"#,
+        );
+
+        let diff = diff_markers(&old, &new, &[String::new()], &DiffOptions::default());
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].from, Point::new(100.0, 0.0));
+        assert_eq!(diff.removed[0].pos, Point::new(500.0, 0.0));
+        assert_eq!(diff.added[0].pos, Point::new(900.0, 0.0));
+    }
+
+    #[test]
+    fn test_diff_markers_by_id() {
+        let old = marker_data(
+            r#"{"id": 7, "label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0},
+               {"id": 8, "label_id": 1, "area_id": 1, "x_pos": 10.0, "y_pos": 0.0}"#,
+        );
+        // 7 moved far away, 8 was replaced by 9 at the same spot.
+        let new = marker_data(
+            r#"{"id": 7, "label_id": 1, "area_id": 1, "x_pos": 1000.0, "y_pos": 0.0},
+               {"id": 9, "label_id": 1, "area_id": 1, "x_pos": 10.0, "y_pos": 0.0}"#,
+        );
+
+        let diff = diff_markers(&old, &new, &[String::new()], &DiffOptions::default());
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].id, Some(7));
+        assert_eq!(diff.removed[0].id, Some(8));
+        assert_eq!(diff.added[0].id, Some(9));
+    }
+
+    #[test]
+    fn test_diff_markers_where() {
+        let old = marker_data(
+            r#"{"label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 1000.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 2000.0, "y_pos": 0.0}"#,
+        );
+        let new = marker_data(
+            r#"{"label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0},
+               {"label_id": 1, "area_id": 1, "x_pos": 1000.0, "y_pos": 0.0}"#,
+        );
+
+        // only what's left of x = 500 is counted, unchanged markers too.
+        let diff = diff_markers_where(
+            &old,
+            &new,
+            &[String::new()],
+            &DiffOptions::default(),
+            |pos| pos.x < 500.0,
+        );
+        assert_eq!(diff.unchanged, 1);
+        assert!(diff.is_empty());
+
+        let diff = diff_markers(&old, &new, &[String::new()], &DiffOptions::default());
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.removed.len(), 1);
+    }
+
+    #[test]
+    fn test_to_text() {
+        let old = marker_data(r#"{"label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0}"#);
+        let new = marker_data("");
+
+        let diff = diff_markers(&old, &new, &[String::new()], &DiffOptions::default());
+        assert_eq!(
+            diff.to_text(),
+            "Sweet Flower: 0 added, 1 removed, 0 moved\n  \
+             - (0, 0)\n\
+             total: 0 added, 1 removed, 0 moved, 0 unchanged\n"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+
+use crate::api::models::{AreaData, Label, Marker, RegionData};
+
+use super::html_to_text;
+
+// everything is in the API's co-ordinates (relative to the map's origin) with y negated,
+// since GeoJSON's y axis points up (north) while the map's points down.
+// load it in QGIS with any simple/engineering CRS, or in leaflet with `CRS.Simple`.
+
+fn to_position(x: f32, y: f32) -> Value {
+    json!([x, -y])
+}
+
+/// counter clockwise ring (as RFC 7946 wants for outer rings) of the bounding box.
+fn to_polygon(lx: f32, ly: f32, rx: f32, ry: f32) -> Value {
+    json!({
+        "type": "Polygon",
+        "coordinates": [[
+            to_position(lx, ly),
+            to_position(lx, ry),
+            to_position(rx, ry),
+            to_position(rx, ly),
+            to_position(lx, ly),
+        ]],
+    })
+}
+
+/// a FeatureCollection of points, one for every marker.
+pub fn markers_to_geojson(matched_markers: &[(&Label, Vec<&Marker>)]) -> Value {
+    let features: Vec<Value> = matched_markers
+        .iter()
+        .flat_map(|(label, markers)| {
+            markers.iter().map(|marker| {
+                let pos = marker.pos();
+                json!({
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Point",
+                        "coordinates": to_position(pos.x, pos.y),
+                    },
+                    "properties": {
+                        "id": marker.id,
+                        "label_id": label.id,
+                        "label": label.name,
+                        "area_id": marker.area_id,
+                        "x": pos.x,
+                        "y": pos.y,
+                        "content": html_to_text(marker.content()),
+                        "image": marker.image,
+                    },
+                })
+            })
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+/// a FeatureCollection of polygons for the bounding boxes of the given areas,
+/// regions and their sub regions. `kind` property tells which one a feature is.
+/// areas are given along with their area id.
+pub fn regions_to_geojson(areas: &[(u8, &AreaData)], regions: &[RegionData]) -> Value {
+    let mut features = vec![];
+
+    for (area_id, area) in areas {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": to_polygon(area.lx, area.ly, area.rx, area.ry),
+            "properties": {
+                "kind": "area",
+                "name": area.name,
+                "area_id": area_id,
+                "map_id": area.map_id,
+            },
+        }));
+    }
+
+    for region in regions {
+        features.push(region_feature(region, "region", None));
+        for sub_region in &region.children {
+            features.push(region_feature(sub_region, "sub_region", Some(&region.name)));
+        }
+    }
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+fn region_feature(region: &RegionData, kind: &str, parent: Option<&str>) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": to_polygon(region.lx, region.ly, region.rx, region.ry),
+        "properties": {
+            "kind": kind,
+            "name": region.name,
+            "area_id": region.area_id,
+            "map_id": region.map_id(),
+            "parent": parent,
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_markers_to_geojson() {
+        let label = Label {
+            name: "Sweet Flower".to_string(),
+            icon: String::new(),
+            id: 3,
+            ..Default::default()
+        };
+        let marker: Marker = serde_json::from_str(
+            r#"{"id": 9, "label_id": 3, "area_id": 1, "x_pos": 1.5, "y_pos": 20.0,
+                                     "content": "<p>near the tree</p>"}"#,
+        )
+        .unwrap();
+
+        let geojson = markers_to_geojson(&[(&label, vec![&marker])]);
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["geometry"]["coordinates"], json!([1.5, -20.0]));
+        assert_eq!(feature["properties"]["label"], "Sweet Flower");
+        assert_eq!(feature["properties"]["area_id"], 1);
+        assert_eq!(feature["properties"]["id"], 9);
+        assert_eq!(feature["properties"]["content"], "near the tree");
+    }
+
+    #[test]
+    fn test_regions_to_geojson() {
+        let regions: Vec<RegionData> = serde_json::from_str(
+            r#"[{"name": "Liyue Harbor", "l_x": 0.0, "l_y": 0.0, "r_x": 10.0, "r_y": 5.0,
+                "area_id": 2, "map_id": "2", "children": [
+                {"name": "Yujing Terrace", "l_x": 1.0, "l_y": 1.0, "r_x": 2.0, "r_y": 2.0,
+                 "area_id": 2, "map_id": "2", "children": []}]}]"#,
+        )
+        .unwrap();
+
+        let geojson = regions_to_geojson(&[], &regions);
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(features[1]["properties"]["parent"], "Liyue Harbor");
+        assert_eq!(
+            features[0]["geometry"]["coordinates"][0][2],
+            json!([10.0, -5.0])
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::Write;
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::render::Viewport;
+use crate::{marker_bg, PIN_SIZE};
+
+use super::{escape_xml, html_to_text, png_data_uri, MarkerLayer};
+
+const STYLE: &str = r#"
+body { margin: 0; font-family: sans-serif; background: #222; color: #eee; }
+#panel { position: fixed; top: 0; left: 0; bottom: 0; width: 240px; overflow-y: auto;
+         padding: 8px; box-sizing: border-box; background: #333; z-index: 2; }
+#panel h1 { font-size: 16px; margin: 0 0 8px; }
+#search { width: 100%; box-sizing: border-box; margin-bottom: 8px; }
+#panel label { display: block; font-size: 13px; margin: 2px 0; }
+#map { position: relative; margin-left: 240px; }
+#map > img { display: block; }
+.marker { position: absolute; width: PIN_SIZEpx; height: PIN_SIZEpx; background-size: 100% 100%; }
+.marker > span { display: block; width: 100%; height: 100%; background-size: 100% 100%; }
+.marker.hidden, .layer-hidden .marker { display: none; }
+#tooltip { position: absolute; display: none; max-width: 280px; padding: 6px; z-index: 3;
+           background: rgba(0, 0, 0, 0.85); border-radius: 4px; font-size: 13px;
+           white-space: pre-line; pointer-events: none; }
+#tooltip img { display: block; max-width: 100%; margin-top: 4px; }
+"#;
+
+// toggling a layer hides its group, searching hides the markers whose name and hint
+// text don't contain the query. the tooltip is filled from the marker's data attributes.
+const SCRIPT: &str = r#"
+const tooltip = document.getElementById('tooltip');
+document.querySelectorAll('#panel input[type=checkbox]').forEach(function (toggle) {
+  toggle.addEventListener('change', function () {
+    document.getElementById('layer-' + toggle.value).classList.toggle('layer-hidden', !toggle.checked);
+  });
+});
+document.getElementById('search').addEventListener('input', function (event) {
+  const query = event.target.value.trim().toLowerCase();
+  document.querySelectorAll('.marker').forEach(function (marker) {
+    marker.classList.toggle('hidden', query !== '' && !marker.dataset.search.includes(query));
+  });
+});
+document.querySelectorAll('.marker').forEach(function (marker) {
+  marker.addEventListener('mouseenter', function () {
+    tooltip.textContent = marker.dataset.title;
+    if (marker.dataset.image) {
+      const image = document.createElement('img');
+      image.src = marker.dataset.image;
+      image.loading = 'lazy';
+      tooltip.appendChild(image);
+    }
+    tooltip.style.left = (marker.offsetLeft + marker.offsetWidth) + 'px';
+    tooltip.style.top = marker.offsetTop + 'px';
+    tooltip.style.display = 'block';
+  });
+  marker.addEventListener('mouseleave', function () {
+    tooltip.style.display = 'none';
+  });
+});
+"#;
+
+/// writes a single HTML page with the map chunk and the markers on it, with everything
+/// (images, styles, script) inlined so that it works offline.
+///
+/// the page has a checkbox per label to toggle its layer, a search box filtering the markers
+/// by name and hint text, and tooltips with the hint text and screenshot (if fetched, see
+/// `RenderOptions::point_details`). screenshots are links to the API, so they need internet.
+pub fn to_html(
+    title: &str,
+    map_chunk: &DynamicImage,
+    viewport: &Viewport,
+    layers: &[MarkerLayer],
+) -> anyhow::Result<String> {
+    let (width, height) = map_chunk.dimensions();
+    let marker_bg = marker_bg()?;
+    let title = escape_xml(title);
+
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, r#"<html lang="en"><head><meta charset="utf-8">"#)?;
+    writeln!(html, "<title>{title}</title>")?;
+    writeln!(
+        html,
+        "<style>{}",
+        STYLE.replace("PIN_SIZE", &PIN_SIZE.to_string())
+    )?;
+    writeln!(
+        html,
+        ".marker {{ background-image: url({}); }}",
+        png_data_uri(marker_bg)?
+    )?;
+    for layer in layers {
+        writeln!(
+            html,
+            ".label-{} > span {{ background-image: url({}); }}",
+            layer.label.id,
+            png_data_uri(&layer.icon)?
+        )?;
+    }
+    writeln!(html, "</style></head><body>")?;
+
+    writeln!(html, r#"<div id="panel"><h1>{title}</h1>"#)?;
+    writeln!(
+        html,
+        r#"<input id="search" type="search" placeholder="search markers">"#
+    )?;
+    for layer in layers {
+        writeln!(
+            html,
+            r#"<label><input type="checkbox" value="{id}" checked> {name} ({count})</label>"#,
+            id = layer.label.id,
+            name = escape_xml(&layer.label.name),
+            count = layer.markers.len(),
+        )?;
+    }
+    writeln!(html, "</div>")?;
+
+    writeln!(
+        html,
+        r#"<div id="map" style="width: {width}px; height: {height}px;">"#
+    )?;
+    writeln!(
+        html,
+        r#"<img src="{}" width="{width}" height="{height}" alt="{title}">"#,
+        png_data_uri(map_chunk)?
+    )?;
+    for layer in layers {
+        writeln!(html, r#"<div class="layer" id="layer-{}">"#, layer.label.id)?;
+        for marker in &layer.markers {
+            let pos = marker.pos();
+            let pixel = viewport.to_pixel(pos);
+            let content = html_to_text(marker.content());
+            let mut tooltip = format!("{} ({}, {})", layer.label.name, pos.x, pos.y);
+            if !content.is_empty() {
+                write!(tooltip, "\n{content}")?;
+            }
+            // pin's tip is at the bottom center, same as the raster output.
+            writeln!(
+                html,
+                r#"<div class="marker label-{id}" style="left: {x}px; top: {y}px;" data-id="{point_id}" data-title="{tooltip}" data-search="{search}" data-image="{image}"><span></span></div>"#,
+                id = layer.label.id,
+                x = pixel.x - (PIN_SIZE / 2) as f32,
+                y = pixel.y - PIN_SIZE as f32,
+                point_id = marker.id.map(|id| id.to_string()).unwrap_or_default(),
+                tooltip = escape_xml(&tooltip),
+                search = escape_xml(&format!("{} {}", layer.label.name, content).to_lowercase()),
+                image = escape_xml(marker.image()),
+            )?;
+        }
+        writeln!(html, "</div>")?;
+    }
+    writeln!(html, r#"<div id="tooltip"></div></div>"#)?;
+
+    writeln!(html, "<script>{SCRIPT}</script>")?;
+    writeln!(html, "</body></html>")?;
+
+    Ok(html)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::models::{Label, Marker};
+    use crate::shapes::{point::Point, rect::Rect};
+
+    #[test]
+    fn test_to_html() {
+        let label = Label {
+            name: "Common Chest".to_string(),
+            icon: String::new(),
+            id: 5,
+            ..Default::default()
+        };
+        let marker: Marker = serde_json::from_str(
+            r#"{"id": 11, "label_id": 5, "area_id": 2, "x_pos": 10.0, "y_pos": -5.0,
+                "content": "<p>behind the <b>rock</b></p>"}"#,
+        )
+        .unwrap();
+        let layers = [MarkerLayer {
+            label: &label,
+            icon: DynamicImage::new_rgba8(4, 4),
+            markers: vec![&marker],
+        }];
+        let viewport = Viewport::new(Point::new(50.0, 50.0), &Rect::new(0, 0, 100, 100));
+
+        let html = to_html(
+            "Mondstadt",
+            &DynamicImage::new_rgba8(100, 100),
+            &viewport,
+            &layers,
+        )
+        .unwrap();
+
+        assert!(html.contains(r#"style="left: 44px; top: 13px;""#));
+        assert!(html.contains("data-title=\"Common Chest (10, -5)\nbehind the rock\""));
+        assert!(html.contains(r#"data-search="common chest behind the rock""#));
+        assert!(html.contains(r#"<input type="checkbox" value="5" checked> Common Chest (1)"#));
+        // nothing is loaded from elsewhere.
+        assert!(!html.contains("http"));
+    }
+}
@@ -0,0 +1,111 @@
+pub mod geojson;
+pub mod html;
+pub mod raster;
+pub mod stats;
+pub mod strips;
+pub mod svg;
+pub mod table;
+pub mod tiles;
+
+use std::io::Cursor;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{DynamicImage, ImageOutputFormat};
+
+use crate::api::models::{Label, Marker};
+
+/// a label with its icon and the markers to be placed on the map.
+pub struct MarkerLayer<'a> {
+    pub label: &'a Label,
+    pub icon: DynamicImage,
+    pub markers: Vec<&'a Marker>,
+}
+
+/// encodes the image as PNG and returns it as a `data:` URI, for embedding in documents.
+pub fn png_data_uri(image: &DynamicImage) -> anyhow::Result<String> {
+    let mut bytes = Cursor::new(vec![]);
+    image.write_to(&mut bytes, ImageOutputFormat::Png)?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        STANDARD.encode(bytes.into_inner())
+    ))
+}
+
+/// escapes text so that it can be placed inside XML/HTML text or attribute values.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// plain text of the point descriptions, which can be HTML. tags are dropped,
+/// line breaks and paragraphs become new lines.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .to_ascii_lowercase();
+        if tag.starts_with("br") || tag.starts_with("p") || tag.starts_with("div") {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        assert_eq!(
+            html_to_text("<p>Under the <b>bridge</b></p><p>Rock &amp; Roll</p>"),
+            "Under the bridge\nRock & Roll"
+        );
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(
+            escape_xml("Rock & <\"Roll\">"),
+            "Rock &amp; &lt;&quot;Roll&quot;&gt;"
+        );
+    }
+
+    #[test]
+    fn test_png_data_uri() {
+        let uri = png_data_uri(&DynamicImage::new_rgba8(1, 1)).unwrap();
+        assert!(uri.starts_with("data:image/png;base64,iVBORw0KGgo"));
+    }
+}
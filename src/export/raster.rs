@@ -0,0 +1,278 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+use crate::selection::MapSelection;
+
+/// how a rendered map is encoded. picked explicitly, the file extension doesn't matter.
+///
+/// written (and parsed) as `png`, `jpeg`, `jpeg:85`, `webp`, `webp:80` or `webp:lossless`.
+/// WebP needs the `webp` feature.
+///
+/// AVIF isn't supported: image 0.24 encodes it with rav1e (a whole AV1 encoder, slow to
+/// build and to encode big maps with), and its dependencies need a newer bytemuck than the
+/// one this crate is locked to. see `AVIF_UNSUPPORTED`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum RasterFormat {
+    #[default]
+    Png,
+    /// quality from 1 to 100.
+    Jpeg { quality: u8 },
+    /// quality from 1 to 100, ignored when lossless.
+    #[cfg(feature = "webp")]
+    WebP { lossless: bool, quality: u8 },
+}
+
+const DEFAULT_QUALITY: u8 = 90;
+
+/// the error for `avif` outputs, see `RasterFormat`.
+pub const AVIF_UNSUPPORTED: &str = "AVIF output isn't supported, use webp or jpeg instead";
+
+impl RasterFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        let (name, option) = match format.split_once(':') {
+            Some((name, option)) => (name, Some(option)),
+            None => (format, None),
+        };
+        let quality = match option {
+            Some(quality) => quality
+                .parse()
+                .ok()
+                .filter(|quality| (1..=100).contains(quality)),
+            None => Some(DEFAULT_QUALITY),
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "png" if option.is_none() => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg { quality: quality? }),
+            #[cfg(feature = "webp")]
+            "webp" if option == Some("lossless") => Some(Self::WebP {
+                lossless: true,
+                quality: DEFAULT_QUALITY,
+            }),
+            #[cfg(feature = "webp")]
+            "webp" => Some(Self::WebP {
+                lossless: false,
+                quality: quality?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// the usual extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg { .. } => "jpg",
+            #[cfg(feature = "webp")]
+            Self::WebP { .. } => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg { .. } => "image/jpeg",
+            #[cfg(feature = "webp")]
+            Self::WebP { .. } => "image/webp",
+        }
+    }
+}
+
+impl fmt::Display for RasterFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Png => write!(f, "png"),
+            Self::Jpeg { quality } => write!(f, "jpeg:{quality}"),
+            #[cfg(feature = "webp")]
+            Self::WebP { lossless: true, .. } => write!(f, "webp:lossless"),
+            #[cfg(feature = "webp")]
+            Self::WebP { quality, .. } => write!(f, "webp:{quality}"),
+        }
+    }
+}
+
+impl TryFrom<String> for RasterFormat {
+    type Error = String;
+
+    fn try_from(format: String) -> Result<Self, Self::Error> {
+        if format.to_ascii_lowercase().starts_with("avif") {
+            return Err(AVIF_UNSUPPORTED.to_string());
+        }
+        Self::parse(&format).ok_or_else(|| format!("unknown image format '{format}'"))
+    }
+}
+
+impl From<RasterFormat> for String {
+    fn from(format: RasterFormat) -> Self {
+        format.to_string()
+    }
+}
+
+/// what the image shows, written into the text chunks of PNGs. other formats leave it out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RasterMetadata {
+    /// the region or area, as the `Title`.
+    pub title: Option<String>,
+    /// the labels of the markers drawn.
+    pub labels: Vec<String>,
+    /// when the newest marker in the map's data was made.
+    pub data_timestamp: Option<String>,
+}
+
+impl RasterMetadata {
+    /// metadata for a render of the selection with the desired labels.
+    pub fn for_selection(selection: &MapSelection, desired_marker_labels: &[String]) -> Self {
+        Self {
+            title: Some(selection.name.clone()),
+            labels: selection
+                .marker_data
+                .matching_labels(desired_marker_labels)
+                .into_iter()
+                .map(|label| label.name.clone())
+                .collect(),
+            data_timestamp: selection.data_timestamp(),
+        }
+    }
+
+    /// the PNG text chunks, as (keyword, text).
+    pub(crate) fn text_chunks(&self) -> Vec<(&'static str, String)> {
+        let mut chunks = vec![("Software", "genshin_map_generator".to_string())];
+        if let Some(title) = &self.title {
+            chunks.push(("Title", title.clone()));
+        }
+        if !self.labels.is_empty() {
+            chunks.push(("Labels", self.labels.join(", ")));
+        }
+        if let Some(data_timestamp) = &self.data_timestamp {
+            chunks.push(("Data Timestamp", data_timestamp.clone()));
+        }
+        chunks
+    }
+}
+
+/// encodes the image in the format, with the metadata if the format supports it.
+pub fn encode(
+    image: &DynamicImage,
+    format: RasterFormat,
+    metadata: Option<&RasterMetadata>,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    match format {
+        RasterFormat::Png => {
+            let image = image.to_rgba8();
+            let mut encoder = png::Encoder::new(writer, image.width(), image.height());
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            for (keyword, text) in metadata
+                .map(RasterMetadata::text_chunks)
+                .unwrap_or_default()
+            {
+                encoder.add_itxt_chunk(keyword.to_string(), text)?;
+            }
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(image.as_raw())?;
+            writer.finish()?;
+        }
+        RasterFormat::Jpeg { quality } => {
+            // jpeg has no alpha.
+            JpegEncoder::new_with_quality(writer, quality).encode_image(&image.to_rgb8())?;
+        }
+        #[cfg(feature = "webp")]
+        RasterFormat::WebP { lossless, quality } => {
+            use image::codecs::webp::{WebPEncoder, WebPQuality};
+
+            let quality = if lossless {
+                WebPQuality::lossless()
+            } else {
+                WebPQuality::lossy(quality)
+            };
+            let image = image.to_rgba8();
+            WebPEncoder::new_with_quality(writer, quality).encode(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                image::ColorType::Rgba8,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// `encode`s the image into the file.
+pub fn save(
+    image: &DynamicImage,
+    format: RasterFormat,
+    metadata: Option<&RasterMetadata>,
+    path: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    encode(image, format, metadata, &mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(RasterFormat::parse("png"), Some(RasterFormat::Png));
+        assert_eq!(
+            RasterFormat::parse("JPG"),
+            Some(RasterFormat::Jpeg { quality: 90 })
+        );
+        assert_eq!(
+            RasterFormat::parse("jpeg:75"),
+            Some(RasterFormat::Jpeg { quality: 75 })
+        );
+        assert_eq!(RasterFormat::parse("jpeg:0"), None);
+        assert_eq!(RasterFormat::parse("png:9"), None);
+        assert_eq!(RasterFormat::parse("gif"), None);
+        assert_eq!(
+            RasterFormat::try_from("avif:80".to_string()),
+            Err(AVIF_UNSUPPORTED.to_string())
+        );
+        assert_eq!(
+            RasterFormat::parse(&RasterFormat::Jpeg { quality: 60 }.to_string()),
+            Some(RasterFormat::Jpeg { quality: 60 })
+        );
+    }
+
+    #[test]
+    fn test_png_metadata() {
+        let image = DynamicImage::new_rgba8(4, 4);
+        let metadata = RasterMetadata {
+            title: Some("Vimara Village".to_string()),
+            labels: vec!["Padisarah".to_string(), "Teleport Waypoint".to_string()],
+            data_timestamp: Some("2023-03-01 10:00:00".to_string()),
+        };
+        let mut bytes = vec![];
+        encode(&image, RasterFormat::Png, Some(&metadata), &mut bytes).unwrap();
+
+        let reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let chunks: Vec<(String, String)> = reader
+            .info()
+            .utf8_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.get_text().unwrap()))
+            .collect();
+        assert!(chunks.contains(&("Title".to_string(), "Vimara Village".to_string())));
+        assert!(chunks.contains(&(
+            "Labels".to_string(),
+            "Padisarah, Teleport Waypoint".to_string()
+        )));
+        assert!(chunks.contains(&(
+            "Data Timestamp".to_string(),
+            "2023-03-01 10:00:00".to_string()
+        )));
+
+        assert!(image::load_from_memory(&bytes).is_ok());
+    }
+}
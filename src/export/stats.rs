@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::Serialize;
+
+use crate::render::{draw, font};
+
+use super::table::MarkerRow;
+
+const BACKGROUND: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const TEXT: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const BAR: Rgba<u8> = Rgba([30, 144, 255, 255]);
+
+/// a name and how many markers are in it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Count {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionCount {
+    pub name: String,
+    /// markers in the region, including the ones in its sub regions.
+    pub count: usize,
+    pub sub_regions: Vec<Count>,
+}
+
+/// how the markers of a label are spread over the map.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LabelStats {
+    pub label: String,
+    pub label_id: i32,
+    pub total: usize,
+    pub areas: Vec<Count>,
+    pub regions: Vec<RegionCount>,
+    /// markers that aren't inside the frame of any region.
+    pub outside_regions: usize,
+}
+
+/// marker counts per label per area, region and sub region of a map.
+/// counts are sorted with the biggest first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsReport {
+    pub map_id: u8,
+    pub labels: Vec<LabelStats>,
+}
+
+impl StatsReport {
+    /// counts the rows, see `table::marker_rows`. labels stay in the order they first appear.
+    /// `area_names` maps area ids to their names, unknown ids are shown as "area {id}".
+    pub fn from_rows(map_id: u8, rows: &[MarkerRow], area_names: &HashMap<u8, String>) -> Self {
+        let mut labels: Vec<LabelStats> = vec![];
+
+        for row in rows {
+            let stats = match labels
+                .iter()
+                .position(|stats| stats.label_id == row.label_id)
+            {
+                Some(i) => &mut labels[i],
+                None => {
+                    labels.push(LabelStats {
+                        label: row.label.clone(),
+                        label_id: row.label_id,
+                        total: 0,
+                        areas: vec![],
+                        regions: vec![],
+                        outside_regions: 0,
+                    });
+                    labels.last_mut().unwrap()
+                }
+            };
+
+            stats.total += 1;
+            let area = area_names
+                .get(&row.area_id)
+                .cloned()
+                .unwrap_or_else(|| format!("area {}", row.area_id));
+            increment(&mut stats.areas, &area);
+
+            match &row.region {
+                Some(region) => {
+                    let i = match stats.regions.iter().position(|r| &r.name == region) {
+                        Some(i) => i,
+                        None => {
+                            stats.regions.push(RegionCount {
+                                name: region.clone(),
+                                count: 0,
+                                sub_regions: vec![],
+                            });
+                            stats.regions.len() - 1
+                        }
+                    };
+                    let region = &mut stats.regions[i];
+                    region.count += 1;
+                    if let Some(sub_region) = &row.sub_region {
+                        increment(&mut region.sub_regions, sub_region);
+                    }
+                }
+                None => stats.outside_regions += 1,
+            }
+        }
+
+        for stats in &mut labels {
+            sort_counts(&mut stats.areas);
+            stats
+                .regions
+                .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+            for region in &mut stats.regions {
+                sort_counts(&mut region.sub_regions);
+            }
+        }
+
+        Self { map_id, labels }
+    }
+
+    /// one section per label with a table of areas and one of regions and sub regions.
+    pub fn to_markdown(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "# Marker statistics of map {}", self.map_id);
+
+        for stats in &self.labels {
+            let _ = writeln!(text, "\n## {} ({})\n", escape_md(&stats.label), stats.total);
+
+            let _ = writeln!(text, "| Area | Count |\n| --- | ---: |");
+            for area in &stats.areas {
+                let _ = writeln!(text, "| {} | {} |", escape_md(&area.name), area.count);
+            }
+
+            let _ = writeln!(
+                text,
+                "\n| Region | Sub region | Count |\n| --- | --- | ---: |"
+            );
+            for region in &stats.regions {
+                let _ = writeln!(text, "| {} | | {} |", escape_md(&region.name), region.count);
+                for sub_region in &region.sub_regions {
+                    let _ = writeln!(
+                        text,
+                        "| | {} | {} |",
+                        escape_md(&sub_region.name),
+                        sub_region.count
+                    );
+                }
+            }
+            if stats.outside_regions > 0 {
+                let _ = writeln!(text, "| *outside regions* | | {} |", stats.outside_regions);
+            }
+        }
+
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    /// horizontal bar chart of the label's markers per region, biggest first.
+    /// `None` if the label isn't in the report.
+    pub fn bar_chart(&self, label_id: i32) -> Option<DynamicImage> {
+        let stats = self
+            .labels
+            .iter()
+            .find(|stats| stats.label_id == label_id)?;
+        Some(bar_chart(stats))
+    }
+}
+
+fn increment(counts: &mut Vec<Count>, name: &str) {
+    match counts.iter_mut().find(|count| count.name == name) {
+        Some(count) => count.count += 1,
+        None => counts.push(Count {
+            name: name.to_string(),
+            count: 1,
+        }),
+    }
+}
+
+fn sort_counts(counts: &mut [Count]) {
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// pipes would end the table cell early.
+fn escape_md(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn bar_chart(stats: &LabelStats) -> DynamicImage {
+    let text_scale = 2;
+    let margin = 10;
+    let bar_height = font::text_height(text_scale) + 8;
+    let max_bar_width = 400;
+
+    let mut rows: Vec<(String, usize)> = stats
+        .regions
+        .iter()
+        .map(|region| (region.name.clone(), region.count))
+        .collect();
+    if stats.outside_regions > 0 {
+        rows.push(("outside regions".to_string(), stats.outside_regions));
+    }
+
+    let title = format!("{} ({})", stats.label, stats.total);
+    let name_width = rows
+        .iter()
+        .map(|(name, _)| font::text_width(name, text_scale))
+        .max()
+        .unwrap_or(0);
+    let count_width = font::text_width(&stats.total.to_string(), text_scale);
+    let width = (2 * margin + name_width + margin + max_bar_width + margin + count_width)
+        .max(2 * margin + font::text_width(&title, text_scale));
+    let height = 2 * margin + (rows.len() as u32 + 1) * (bar_height + 4);
+
+    let mut canvas = RgbaImage::from_pixel(width, height, BACKGROUND);
+    font::draw_text(
+        &mut canvas,
+        margin as i64,
+        margin as i64,
+        &title,
+        text_scale,
+        TEXT,
+    );
+
+    let max_count = rows
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let bar_x = (2 * margin + name_width) as i64;
+    for (i, (name, count)) in rows.iter().enumerate() {
+        let y = (margin + (i as u32 + 1) * (bar_height + 4)) as i64;
+        let text_y = y + 4;
+        let bar_width = (max_bar_width as usize * count / max_count).max(1) as u32;
+
+        font::draw_text(&mut canvas, margin as i64, text_y, name, text_scale, TEXT);
+        draw::fill_rect(&mut canvas, bar_x, y, bar_width, bar_height, BAR);
+        font::draw_text(
+            &mut canvas,
+            bar_x + bar_width as i64 + margin as i64,
+            text_y,
+            &count.to_string(),
+            text_scale,
+            TEXT,
+        );
+    }
+
+    DynamicImage::ImageRgba8(canvas)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(label_id: i32, region: Option<&str>, sub_region: Option<&str>) -> MarkerRow {
+        MarkerRow {
+            label: format!("label {}", label_id),
+            region: region.map(str::to_string),
+            sub_region: sub_region.map(str::to_string),
+            x: 0.0,
+            y: 0.0,
+            label_id,
+            area_id: 3,
+        }
+    }
+
+    fn test_report() -> StatsReport {
+        let rows = [
+            row(1, Some("Avidya Forest"), Some("Vimara Village")),
+            row(1, Some("Avidya Forest"), None),
+            row(1, Some("Lokapala Jungle"), None),
+            row(1, None, None),
+            row(2, Some("Lokapala Jungle"), None),
+        ];
+        let area_names = HashMap::from([(3, "Sumeru".to_string())]);
+        StatsReport::from_rows(2, &rows, &area_names)
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let report = test_report();
+
+        assert_eq!(report.labels.len(), 2);
+        let stats = &report.labels[0];
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.outside_regions, 1);
+        assert_eq!(
+            stats.areas,
+            vec![Count {
+                name: "Sumeru".to_string(),
+                count: 4
+            }]
+        );
+        assert_eq!(stats.regions[0].name, "Avidya Forest");
+        assert_eq!(stats.regions[0].count, 2);
+        assert_eq!(stats.regions[0].sub_regions[0].count, 1);
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let markdown = test_report().to_markdown();
+
+        assert!(markdown.starts_with("# Marker statistics of map 2\n\n## label 1 (4)\n"));
+        assert!(markdown.contains("| Avidya Forest | | 2 |\n| | Vimara Village | 1 |\n"));
+        assert!(markdown.contains("| *outside regions* | | 1 |\n"));
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let report = test_report();
+
+        assert!(report.bar_chart(1).is_some());
+        assert!(report.bar_chart(42).is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use super::raster::RasterMetadata;
+use super::tiles::{block_markers, TileMarkers};
+use crate::api::client::ApiClient;
+use crate::api::models::MapData;
+use crate::overlay_markers_hd;
+use crate::shapes::rect::Rect;
+
+/// the rows of each strip of the frame, as (top, bottom) in absolute co-ordinates.
+///
+/// strips are cut at multiples of `strip_height` on the whole map (not the frame), so with
+/// the height of a map slice each slice is fetched and decoded once.
+pub fn strip_bounds(frame: &Rect, strip_height: u32) -> Vec<(u32, u32)> {
+    let strip_height = strip_height.max(1);
+    let mut bounds = vec![];
+    let mut top = frame.ly;
+    while top < frame.ry {
+        let bottom = ((top / strip_height + 1) * strip_height).min(frame.ry);
+        bounds.push((top, bottom));
+        top = bottom;
+    }
+    bounds
+}
+
+/// renders the frame of the map into `writer` as a PNG, a strip of rows at a time, so that
+/// only a strip (and the slices it needs) is ever in memory. for outputs too big to render
+/// with `MapGenerator::render`.
+///
+/// `markers` are placed at absolute positions like with `export_tiles`, each strip gets the
+/// markers whose icons reach into it. strips are as tall as a map slice unless
+/// `strip_height` is given. the metadata goes in text chunks like with `raster::encode`.
+pub async fn write_png_strips(
+    client: &ApiClient,
+    map_data: &MapData,
+    frame: &Rect,
+    markers: &TileMarkers,
+    strip_height: Option<u32>,
+    metadata: Option<&RasterMetadata>,
+    writer: impl Write,
+) -> anyhow::Result<()> {
+    let strip_height = match strip_height {
+        Some(strip_height) => strip_height,
+        None => {
+            let first_slice = map_data
+                .slices
+                .first()
+                .and_then(|row| row.first())
+                .and_then(|slice| slice.get("url"))
+                .ok_or_else(|| anyhow::anyhow!("the map has no slices"))?;
+            client.fetch_image(first_slice).await?.height()
+        }
+    };
+
+    let mut encoder = png::Encoder::new(writer, frame.width(), frame.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in metadata
+        .map(RasterMetadata::text_chunks)
+        .unwrap_or_default()
+    {
+        encoder.add_itxt_chunk(keyword.to_string(), text)?;
+    }
+    let mut png_writer = encoder.write_header()?;
+    let mut stream = png_writer.stream_writer()?;
+
+    for (top, bottom) in strip_bounds(frame, strip_height) {
+        let strip_frame = Rect::new(frame.lx, top, frame.rx, bottom);
+        let Some(mut strip) = client.get_map_chunk(map_data, &strip_frame).await else {
+            anyhow::bail!("could not fetch the map chunk {:?}", strip_frame);
+        };
+        overlay_markers_hd(&mut strip, block_markers(markers, &strip_frame))?;
+
+        stream.write_all(strip.into_rgba8().as_raw())?;
+        tracing::debug!(top, bottom, "wrote a strip");
+    }
+    stream.finish()?;
+    png_writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_strip_bounds() {
+        assert_eq!(
+            strip_bounds(&Rect::new(0, 100, 50, 700), 256),
+            vec![(100, 256), (256, 512), (512, 700)]
+        );
+        assert_eq!(
+            strip_bounds(&Rect::new(0, 0, 50, 512), 256),
+            vec![(0, 256), (256, 512)]
+        );
+        assert_eq!(strip_bounds(&Rect::new(0, 10, 50, 10), 256), vec![]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::render::Viewport;
+use crate::{marker_bg, PIN_SIZE};
+
+use super::{escape_xml, html_to_text, png_data_uri, MarkerLayer};
+
+/// writes an SVG with the map chunk embedded as the background and the markers on top.
+///
+/// every label becomes a `<symbol>` (pin background + icon) and every marker a `<use>` of it
+/// with a `<title>` tooltip. markers are grouped per label in `<g class="layer">`, and carry
+/// `data-*` attributes so that web pages can make them interactive.
+pub fn to_svg(
+    map_chunk: &DynamicImage,
+    viewport: &Viewport,
+    layers: &[MarkerLayer],
+) -> anyhow::Result<String> {
+    let (width, height) = map_chunk.dimensions();
+    let marker_bg = marker_bg()?;
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+
+    writeln!(svg, "<defs>")?;
+    writeln!(
+        svg,
+        r#"<symbol id="marker-bg" viewBox="0 0 {PIN_SIZE} {PIN_SIZE}"><image width="{PIN_SIZE}" height="{PIN_SIZE}" xlink:href="{}"/></symbol>"#,
+        png_data_uri(marker_bg)?
+    )?;
+    for layer in layers {
+        writeln!(
+            svg,
+            r##"<symbol id="label-{id}" viewBox="0 0 {PIN_SIZE} {PIN_SIZE}"><use xlink:href="#marker-bg" width="{PIN_SIZE}" height="{PIN_SIZE}"/><image width="{PIN_SIZE}" height="{PIN_SIZE}" xlink:href="{icon}"/></symbol>"##,
+            id = layer.label.id,
+            icon = png_data_uri(&layer.icon)?,
+        )?;
+    }
+    writeln!(svg, "</defs>")?;
+
+    writeln!(
+        svg,
+        r#"<image id="base-map" x="0" y="0" width="{width}" height="{height}" xlink:href="{}"/>"#,
+        png_data_uri(map_chunk)?
+    )?;
+
+    writeln!(svg, r#"<g id="markers">"#)?;
+    for layer in layers {
+        let name = escape_xml(&layer.label.name);
+        writeln!(
+            svg,
+            r#"<g class="layer" id="layer-{}" data-label="{name}">"#,
+            layer.label.id
+        )?;
+        for marker in &layer.markers {
+            let pos = marker.pos();
+            let pixel = viewport.to_pixel(pos);
+            // the hint text goes in the tooltip under the name, the screenshot is left to
+            // the page (data-image) as tooltips can't show images.
+            let mut title = format!("{name} ({}, {})", pos.x, pos.y);
+            let content = html_to_text(marker.content());
+            if !content.is_empty() {
+                write!(title, "\n{}", escape_xml(&content))?;
+            }
+            // pin's tip is at the bottom center, same as the raster output.
+            writeln!(
+                svg,
+                r##"<use class="marker" xlink:href="#label-{id}" x="{x}" y="{y}" width="{PIN_SIZE}" height="{PIN_SIZE}" data-id="{point_id}" data-label-id="{id}" data-x="{mx}" data-y="{my}" data-image="{image}"><title>{title}</title></use>"##,
+                id = layer.label.id,
+                point_id = marker.id.map(|id| id.to_string()).unwrap_or_default(),
+                x = pixel.x - (PIN_SIZE / 2) as f32,
+                y = pixel.y - PIN_SIZE as f32,
+                mx = pos.x,
+                my = pos.y,
+                image = escape_xml(marker.image()),
+            )?;
+        }
+        writeln!(svg, "</g>")?;
+    }
+    writeln!(svg, "</g>")?;
+    writeln!(svg, "</svg>")?;
+
+    Ok(svg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::api::models::{Label, Marker};
+    use crate::shapes::{point::Point, rect::Rect};
+
+    #[test]
+    fn test_to_svg() {
+        let label = Label {
+            name: "Cor Lapis".to_string(),
+            icon: String::new(),
+            id: 7,
+            ..Default::default()
+        };
+        let marker: Marker =
+            serde_json::from_str(r#"{"label_id": 7, "area_id": 2, "x_pos": 10.0, "y_pos": -5.0,
+                                     "content": "on the cliff", "img": "https://example.com/a.png"}"#)
+                .unwrap();
+        let layers = [MarkerLayer {
+            label: &label,
+            icon: DynamicImage::new_rgba8(4, 4),
+            markers: vec![&marker],
+        }];
+        let viewport = Viewport::new(Point::new(50.0, 50.0), &Rect::new(0, 0, 100, 100));
+
+        let svg = to_svg(&DynamicImage::new_rgba8(100, 100), &viewport, &layers).unwrap();
+
+        assert!(svg.contains("<title>Cor Lapis (10, -5)\non the cliff</title>"));
+        assert!(svg.contains(r#"data-image="https://example.com/a.png""#));
+        assert!(svg.contains(r#"x="44" y="13""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
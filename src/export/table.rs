@@ -0,0 +1,164 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::api::models::{Label, MarkerData, RegionData};
+use crate::shapes::point::Point;
+use crate::spatial::MarkerIndex;
+
+/// a marker joined with its label and the region it's in.
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerRow {
+    pub label: String,
+    pub region: Option<String>,
+    pub sub_region: Option<String>,
+    pub x: f32,
+    pub y: f32,
+    pub label_id: i32,
+    pub area_id: u8,
+}
+
+impl MarkerRow {
+    const HEADER: [&'static str; 7] = [
+        "label",
+        "region",
+        "sub_region",
+        "x",
+        "y",
+        "label_id",
+        "area_id",
+    ];
+
+    fn fields(&self) -> [String; 7] {
+        [
+            self.label.clone(),
+            self.region.clone().unwrap_or_default(),
+            self.sub_region.clone().unwrap_or_default(),
+            self.x.to_string(),
+            self.y.to_string(),
+            self.label_id.to_string(),
+            self.area_id.to_string(),
+        ]
+    }
+}
+
+/// finds the region and sub region whose frames contain the point.
+/// sub regions are checked first, as regions of the API can overlap a bit.
+pub fn assign_region<'a>(
+    regions: &'a [RegionData],
+    pos: &Point,
+) -> (Option<&'a RegionData>, Option<&'a RegionData>) {
+    for region in regions {
+        if let Some(sub_region) = region.children.iter().find(|child| child.contains(pos)) {
+            return (Some(region), Some(sub_region));
+        }
+    }
+    (regions.iter().find(|region| region.contains(pos)), None)
+}
+
+/// one row for every marker of the given labels, in the order of `labels`.
+pub fn marker_rows(
+    marker_data: &MarkerData,
+    labels: &[&Label],
+    regions: &[RegionData],
+) -> Vec<MarkerRow> {
+    let index = MarkerIndex::new(marker_data);
+
+    labels
+        .iter()
+        .flat_map(|label| {
+            index.with_label(label.id).map(move |marker| {
+                let pos = marker.pos();
+                let (region, sub_region) = assign_region(regions, &pos);
+                MarkerRow {
+                    label: label.name.clone(),
+                    region: region.map(|region| region.name.clone()),
+                    sub_region: sub_region.map(|region| region.name.clone()),
+                    x: pos.x,
+                    y: pos.y,
+                    label_id: label.id,
+                    area_id: marker.area_id,
+                }
+            })
+        })
+        .collect()
+}
+
+/// writes the rows as CSV (RFC 4180) with a header row.
+pub fn write_csv(rows: &[MarkerRow], mut writer: impl Write) -> anyhow::Result<()> {
+    writeln!(writer, "{}", MarkerRow::HEADER.join(","))?;
+    for row in rows {
+        let fields: Vec<String> = row.fields().iter().map(|f| csv_field(f)).collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// writes the rows as JSON Lines, one JSON object per line.
+pub fn write_jsonl(rows: &[MarkerRow], mut writer: impl Write) -> anyhow::Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut writer, row)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// quotes the field if it has a separator, quote or a new line in it.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_data() -> (MarkerData, Vec<RegionData>) {
+        let marker_data: MarkerData = serde_json::from_str(
+            r#"{"point_list": [
+                {"label_id": 1, "area_id": 3, "x_pos": 5.0, "y_pos": 5.0},
+                {"label_id": 1, "area_id": 3, "x_pos": 50.0, "y_pos": 50.0},
+                {"label_id": 2, "area_id": 3, "x_pos": 1.0, "y_pos": 1.0}],
+              "label_list": [{"name": "Nilotpala Lotus, \"Sumeru\"", "icon": "", "id": 1}]}"#,
+        )
+        .unwrap();
+        let regions: Vec<RegionData> = serde_json::from_str(
+            r#"[{"name": "Avidya Forest", "l_x": 0.0, "l_y": 0.0, "r_x": 20.0, "r_y": 20.0,
+                "area_id": 3, "map_id": "2", "children": [
+                {"name": "Vimara Village", "l_x": 4.0, "l_y": 4.0, "r_x": 6.0, "r_y": 6.0,
+                 "area_id": 3, "map_id": "2", "children": []}]}]"#,
+        )
+        .unwrap();
+        (marker_data, regions)
+    }
+
+    #[test]
+    fn test_marker_rows() {
+        let (marker_data, regions) = test_data();
+        let labels: Vec<&Label> = marker_data.labels.iter().collect();
+        let rows = marker_rows(&marker_data, &labels, &regions);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].region.as_deref(), Some("Avidya Forest"));
+        assert_eq!(rows[0].sub_region.as_deref(), Some("Vimara Village"));
+        assert_eq!(rows[1].region, None);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let (marker_data, regions) = test_data();
+        let labels: Vec<&Label> = marker_data.labels.iter().collect();
+        let rows = marker_rows(&marker_data, &labels, &regions);
+
+        let mut csv = vec![];
+        write_csv(&rows[..1], &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "label,region,sub_region,x,y,label_id,area_id\n\
+             \"Nilotpala Lotus, \"\"Sumeru\"\"\",Avidya Forest,Vimara Village,5,5,1,3\n"
+        );
+    }
+}
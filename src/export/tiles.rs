@@ -0,0 +1,455 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{imageops::FilterType, DynamicImage, GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::api::client::ApiClient;
+use crate::api::models::MapData;
+use crate::shapes::{point::Point, rect::Rect};
+use crate::{overlay_markers_hd, overlay_markers_sized, PIN_SIZE};
+
+/// how tile rows are numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TileScheme {
+    /// y = 0 is the top row (Leaflet, OpenLayers, google maps)
+    Xyz,
+    /// y = 0 is the bottom row
+    Tms,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TileOptions {
+    /// width and height of a tile in pixels.
+    pub tile_size: u32,
+    pub scheme: TileScheme,
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        Self {
+            tile_size: 256,
+            scheme: TileScheme::Xyz,
+        }
+    }
+}
+
+/// written next to the tiles as `metadata.json`.
+///
+/// at `max_zoom` one tile pixel is one map pixel, every zoom level below halves that.
+/// to place something at API co-ordinates (x, y) with Leaflet's `CRS.Simple`, use pixel
+/// `(x + origin.0, y + origin.1)` and `map.unproject(pixel, max_zoom)`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileMetadata {
+    pub map_id: u8,
+    pub tile_size: u32,
+    pub scheme: TileScheme,
+    pub min_zoom: u32,
+    pub max_zoom: u32,
+    /// size of the stitched map in pixels, at max zoom.
+    pub map_size: (u32, u32),
+    /// position of the API's origin from the top left of the map.
+    pub origin: (f32, f32),
+    pub total_size: (i32, i32),
+    pub padding: (f32, f32),
+    /// labels baked into the tiles, empty if the tiles have no markers.
+    pub labels: Vec<String>,
+}
+
+/// markers to be baked into the tiles: an icon and the absolute (from top left of the map)
+/// positions to place it at.
+pub type TileMarkers = Vec<(DynamicImage, Vec<Point>)>;
+
+/// how a map is cut into tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileLayout {
+    pub tile_size: u32,
+    /// size of a map slice in pixels.
+    pub slice_size: (u32, u32),
+    /// size of the stitched map in pixels, at max zoom.
+    pub map_size: (u32, u32),
+    pub max_zoom: u32,
+}
+
+impl TileLayout {
+    /// the layout of the map's tiles, fetches the first slice for the size of the slices.
+    pub async fn new(
+        client: &ApiClient,
+        map_data: &MapData,
+        tile_size: u32,
+    ) -> anyhow::Result<Self> {
+        let first_slice = map_data
+            .slices
+            .first()
+            .and_then(|row| row.first())
+            .and_then(|slice| slice.get("url"))
+            .ok_or_else(|| anyhow::anyhow!("the map has no slices"))?;
+        let slice_size = client.fetch_image(first_slice).await?.dimensions();
+        Ok(Self::from_slices(map_data, slice_size, tile_size))
+    }
+
+    fn from_slices(map_data: &MapData, slice_size: (u32, u32), tile_size: u32) -> Self {
+        let tile_size = tile_size.max(1);
+        let columns = map_data.slices.iter().map(Vec::len).max().unwrap_or(0) as u32;
+        let map_size = (
+            slice_size.0 * columns,
+            slice_size.1 * map_data.slices.len() as u32,
+        );
+        Self {
+            tile_size,
+            slice_size,
+            map_size,
+            max_zoom: zoom_levels(map_size.0.max(map_size.1), tile_size),
+        }
+    }
+
+    /// the frame of the map (from the top left, in pixels at max zoom) that the tile covers,
+    /// with y = 0 at the top. `None` if the tile is off the map.
+    pub fn tile_frame(&self, zoom: u32, x: u32, y: u32) -> Option<Rect> {
+        if zoom > self.max_zoom {
+            return None;
+        }
+        let size = self.tile_size << (self.max_zoom - zoom);
+        let (lx, ly) = (x.checked_mul(size)?, y.checked_mul(size)?);
+        if lx >= self.map_size.0 || ly >= self.map_size.1 {
+            return None;
+        }
+        Some(Rect::new(lx, ly, lx + size, ly + size))
+    }
+}
+
+/// everything needed to render the tiles of a map with markers, one at a time with
+/// `render_tile`.
+pub struct TileSource {
+    pub map_id: u8,
+    pub map_data: MapData,
+    pub layout: TileLayout,
+    pub markers: TileMarkers,
+    /// labels of the markers.
+    pub labels: Vec<String>,
+}
+
+/// renders a single tile of the pyramid `export_tiles` makes (XYZ numbered), for serving
+/// tiles on demand. `None` if the tile is off the map.
+///
+/// the slices under the tile are fetched and scaled down to the zoom level one at a time, so
+/// even at the lowest zoom only a slice and the tile are in memory. the pins are scaled down
+/// with the map, like in the exported pyramid.
+pub async fn render_tile(
+    client: &ApiClient,
+    source: &TileSource,
+    zoom: u32,
+    x: u32,
+    y: u32,
+) -> anyhow::Result<Option<DynamicImage>> {
+    let layout = &source.layout;
+    let Some(frame) = layout.tile_frame(zoom, x, y) else {
+        return Ok(None);
+    };
+    // map pixels per tile pixel.
+    let factor = 1 << (layout.max_zoom - zoom);
+    let (slice_width, slice_height) = layout.slice_size;
+
+    let mut tile = RgbaImage::new(layout.tile_size, layout.tile_size);
+    for (slice_y, row) in (0..).zip(source.map_data.slices.iter()) {
+        for (slice_x, slice) in (0..).zip(row.iter()) {
+            let slice_r = Rect::new(
+                slice_x * slice_width,
+                slice_y * slice_height,
+                (slice_x + 1) * slice_width,
+                (slice_y + 1) * slice_height,
+            );
+            let Some(common) = slice_r.common(&frame) else {
+                continue;
+            };
+            let Some(url) = slice.get("url") else {
+                continue;
+            };
+            let part = client
+                .fetch_image(url)
+                .await?
+                .crop_imm(
+                    common.lx - slice_r.lx,
+                    common.ly - slice_r.ly,
+                    common.width(),
+                    common.height(),
+                )
+                .resize_exact(
+                    common.width().div_ceil(factor).max(1),
+                    common.height().div_ceil(factor).max(1),
+                    FilterType::Triangle,
+                );
+            image::imageops::replace(
+                &mut tile,
+                &part.to_rgba8(),
+                ((common.lx - frame.lx) / factor) as i64,
+                ((common.ly - frame.ly) / factor) as i64,
+            );
+        }
+    }
+
+    let mut tile = DynamicImage::ImageRgba8(tile);
+    let markers = block_markers(&source.markers, &frame)
+        .into_iter()
+        .map(|(icon, points)| {
+            let points: Vec<Point> = points
+                .map(|point| Point::new(point.x / factor as f32, point.y / factor as f32))
+                .collect();
+            (icon, points.into_iter())
+        })
+        .collect();
+    overlay_markers_sized(&mut tile, markers, (PIN_SIZE / factor).max(1))?;
+    Ok(Some(tile))
+}
+
+/// exports the whole map as a tile pyramid in `out_dir/{z}/{x}/{y}.png`.
+///
+/// the max zoom level is cut straight from the map slices, a block of slices at a time,
+/// so the whole map never has to be in memory. lower levels are built by downscaling
+/// the tiles of the level above.
+pub async fn export_tiles(
+    client: &ApiClient,
+    map_id: u8,
+    map_data: &MapData,
+    markers: &TileMarkers,
+    labels: Vec<String>,
+    options: &TileOptions,
+    out_dir: &Path,
+) -> anyhow::Result<TileMetadata> {
+    let TileLayout {
+        tile_size,
+        slice_size: (slice_width, slice_height),
+        map_size,
+        max_zoom,
+    } = TileLayout::new(client, map_data, options.tile_size).await?;
+
+    let tiles_x = map_size.0.div_ceil(tile_size);
+    let tiles_y = map_size.1.div_ceil(tile_size);
+    // roughly one slice per block, so each slice is decoded about once.
+    let block = (slice_width.max(slice_height) / tile_size).max(1);
+
+    for block_y in (0..tiles_y).step_by(block as usize) {
+        for block_x in (0..tiles_x).step_by(block as usize) {
+            let frame = Rect::new(
+                block_x * tile_size,
+                block_y * tile_size,
+                (block_x + block).min(tiles_x) * tile_size,
+                (block_y + block).min(tiles_y) * tile_size,
+            );
+            let Some(mut chunk) = client.get_map_chunk(map_data, &frame).await else {
+                anyhow::bail!("could not fetch the map chunk {:?}", frame);
+            };
+            overlay_markers_hd(&mut chunk, block_markers(markers, &frame))?;
+
+            for y in block_y..(block_y + block).min(tiles_y) {
+                for x in block_x..(block_x + block).min(tiles_x) {
+                    let tile = chunk
+                        .view(
+                            (x - block_x) * tile_size,
+                            (y - block_y) * tile_size,
+                            tile_size,
+                            tile_size,
+                        )
+                        .to_image();
+                    save_tile(&tile, &tile_path(out_dir, options.scheme, max_zoom, x, y))?;
+                }
+            }
+        }
+    }
+
+    let (mut tiles_x, mut tiles_y) = (tiles_x, tiles_y);
+    for zoom in (0..max_zoom).rev() {
+        tiles_x = tiles_x.div_ceil(2);
+        tiles_y = tiles_y.div_ceil(2);
+        for y in 0..tiles_y {
+            for x in 0..tiles_x {
+                downscale_tile(out_dir, options.scheme, tile_size, zoom, x, y)?;
+            }
+        }
+    }
+
+    let metadata = TileMetadata {
+        map_id,
+        tile_size,
+        scheme: options.scheme,
+        min_zoom: 0,
+        max_zoom,
+        map_size,
+        origin: (map_data.origin().x, map_data.origin().y),
+        total_size: map_data.total_size(),
+        padding: map_data.padding,
+        labels,
+    };
+    fs::write(
+        out_dir.join("metadata.json"),
+        serde_json::to_string_pretty(&metadata)?,
+    )?;
+
+    Ok(metadata)
+}
+
+/// number of times the map has to be halved until it fits in a single tile.
+fn zoom_levels(map_size: u32, tile_size: u32) -> u32 {
+    let mut zoom = 0;
+    while (tile_size << zoom) < map_size {
+        zoom += 1;
+    }
+    zoom
+}
+
+/// path of the tile, y is flipped for TMS.
+fn tile_path(out_dir: &Path, scheme: TileScheme, zoom: u32, x: u32, y: u32) -> PathBuf {
+    let y = match scheme {
+        TileScheme::Xyz => y,
+        TileScheme::Tms => (1 << zoom) - 1 - y,
+    };
+    out_dir
+        .join(zoom.to_string())
+        .join(x.to_string())
+        .join(format!("{y}.png"))
+}
+
+fn save_tile(tile: &RgbaImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    tile.save(path)?;
+    Ok(())
+}
+
+/// builds a tile from the (up to) 4 tiles below it in the next zoom level.
+/// nothing is written if none of those exist.
+fn downscale_tile(
+    out_dir: &Path,
+    scheme: TileScheme,
+    tile_size: u32,
+    zoom: u32,
+    x: u32,
+    y: u32,
+) -> anyhow::Result<()> {
+    let mut canvas = RgbaImage::new(tile_size * 2, tile_size * 2);
+    let mut found = false;
+    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let child = tile_path(out_dir, scheme, zoom + 1, x * 2 + dx, y * 2 + dy);
+        if !child.exists() {
+            continue;
+        }
+        let child = image::open(child)?.to_rgba8();
+        image::imageops::replace(
+            &mut canvas,
+            &child,
+            (dx * tile_size) as i64,
+            (dy * tile_size) as i64,
+        );
+        found = true;
+    }
+    if !found {
+        return Ok(());
+    }
+
+    let tile = image::imageops::resize(&canvas, tile_size, tile_size, FilterType::Triangle);
+    save_tile(&tile, &tile_path(out_dir, scheme, zoom, x, y))
+}
+
+/// markers whose pin overlaps the frame, translated to the frame.
+/// pins are drawn above their point, so the frame is grown by the size of a pin.
+pub(crate) fn block_markers(
+    markers: &TileMarkers,
+    frame: &Rect,
+) -> Vec<(DynamicImage, std::vec::IntoIter<Point>)> {
+    let pin = PIN_SIZE as f32;
+    let (lx, ly, rx, ry) = (
+        frame.lx as f32 - pin / 2.0,
+        frame.ly as f32,
+        frame.rx as f32 + pin / 2.0,
+        frame.ry as f32 + pin,
+    );
+    markers
+        .iter()
+        .filter_map(|(icon, points)| {
+            let points: Vec<Point> = points
+                .iter()
+                .filter(|point| point.x >= lx && point.x < rx && point.y >= ly && point.y < ry)
+                .map(|point| point.translate_axes(frame.top_left()))
+                .collect();
+            if points.is_empty() {
+                return None;
+            }
+            Some((icon.clone(), points.into_iter()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zoom_levels() {
+        assert_eq!(zoom_levels(256, 256), 0);
+        assert_eq!(zoom_levels(257, 256), 1);
+        assert_eq!(zoom_levels(16384, 256), 6);
+    }
+
+    #[test]
+    fn test_tile_frame() {
+        let layout = TileLayout {
+            tile_size: 256,
+            slice_size: (512, 512),
+            map_size: (1024, 512),
+            max_zoom: 2,
+        };
+        assert_eq!(
+            layout
+                .tile_frame(2, 3, 1)
+                .map(|frame| (frame.lx, frame.ly, frame.rx, frame.ry)),
+            Some((768, 256, 1024, 512))
+        );
+        // the whole map at zoom 0.
+        assert_eq!(
+            layout
+                .tile_frame(0, 0, 0)
+                .map(|frame| (frame.lx, frame.ly, frame.rx, frame.ry)),
+            Some((0, 0, 1024, 1024))
+        );
+        assert!(layout.tile_frame(2, 4, 0).is_none());
+        assert!(layout.tile_frame(1, 0, 1).is_none());
+        assert!(layout.tile_frame(3, 0, 0).is_none());
+    }
+
+    #[test]
+    fn test_tile_path() {
+        let out_dir = Path::new("tiles");
+        assert_eq!(
+            tile_path(out_dir, TileScheme::Xyz, 2, 1, 0),
+            Path::new("tiles/2/1/0.png")
+        );
+        assert_eq!(
+            tile_path(out_dir, TileScheme::Tms, 2, 1, 0),
+            Path::new("tiles/2/1/3.png")
+        );
+    }
+
+    #[test]
+    fn test_block_markers() {
+        let markers = vec![(
+            DynamicImage::new_rgba8(1, 1),
+            vec![
+                Point::new(10.0, 10.0),
+                Point::new(300.0, 300.0),
+                Point::new(100.0, 120.0),
+            ],
+        )];
+        let frame = Rect::new(0, 0, 100, 100);
+        let block = block_markers(&markers, &frame);
+        let points: Vec<Point> = block[0].1.clone().collect();
+
+        // the one just below the frame still pokes its pin into it.
+        assert_eq!(
+            points,
+            vec![Point::new(10.0, 10.0), Point::new(100.0, 120.0)]
+        );
+    }
+}
@@ -1,63 +1,517 @@
 pub mod api;
+pub mod batch;
+pub mod diff;
+pub mod export;
+pub mod preset;
+pub mod progress;
+pub mod render;
+pub mod route;
+pub mod selection;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod shapes;
+pub mod spatial;
 
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
-use image::{DynamicImage, GenericImageView, RgbaImage};
-use serde_json::map::Iter;
-
-use 
-    api::{
-        client::ApiClient,
-        models::{Label, Marker, MarkerData, RegionData},
-    };
-    use shapes::{point::Point, rect::Rect};
+use image::DynamicImage;
+use tracing::Instrument;
 
+use api::{
+    client::{ApiClient, ApiClientBuilder, CachePolicy},
+    models::{AreaData, Label, MapData, Marker, MarkerData, RegionData},
+};
+use diff::{DiffOptions, MarkerDiff};
+use export::{
+    geojson, html,
+    raster::RasterMetadata,
+    stats::StatsReport,
+    strips, svg,
+    table::{self, MarkerRow},
+    tiles::{self, TileLayout, TileMarkers, TileMetadata, TileOptions, TileSource},
+    MarkerLayer,
+};
+use preset::MarkerQuery;
+use progress::{Progress, ProgressHook};
+use render::{
+    annotations,
+    boundaries::{self, Boundary},
+    heatmap, mask, RenderOptions, Viewport,
+};
+use route::{PlannedRoute, RouteOptions};
+use selection::MapSelection;
+use shapes::{point::Point, rect::Rect};
+use spatial::MarkerIndex;
 
 /// overlay the given image (map) with a list of images at given coords.
 /// Teyvat Interactive Map API calls these markers "Points"
 pub fn overlay_markers_hd(
     map: &mut DynamicImage,
     images_with_marker_points: Vec<(DynamicImage, impl Iterator<Item = Point>)>,
-) {
-    let marker_bg = image::open("marker_bg.png").unwrap();
+) -> anyhow::Result<()> {
+    overlay_markers_sized(map, images_with_marker_points, PIN_SIZE)
+}
+
+/// size of the marker pins in pixels, at the normal resolution.
+pub const PIN_SIZE: u32 = 32;
+
+static MARKER_BG: OnceLock<DynamicImage> = OnceLock::new();
+
+/// the background of the marker pins, built into the binary and decoded once.
+pub fn marker_bg() -> anyhow::Result<&'static DynamicImage> {
+    if let Some(marker_bg) = MARKER_BG.get() {
+        return Ok(marker_bg);
+    }
+    let marker_bg = image::load_from_memory(include_bytes!("../marker_bg.png"))?;
+    Ok(MARKER_BG.get_or_init(|| marker_bg))
+}
+
+/// same as `overlay_markers_hd`, with pins of the given size. the icons are resized from
+/// the originals, so bigger pins stay sharp.
+pub fn overlay_markers_sized(
+    map: &mut DynamicImage,
+    images_with_marker_points: Vec<(DynamicImage, impl Iterator<Item = Point>)>,
+    pin_size: u32,
+) -> anyhow::Result<()> {
+    let marker_bg = marker_bg()?;
+    let marker_bg = if marker_bg.width() == pin_size {
+        marker_bg.clone()
+    } else {
+        marker_bg.resize(pin_size, pin_size, image::imageops::FilterType::CatmullRom)
+    };
+    let (half, full) = ((pin_size / 2) as i64, pin_size as i64);
 
     for image_with_marker_points in images_with_marker_points {
         let (image, marker_points) = image_with_marker_points;
+        let image = image.resize(pin_size, pin_size, image::imageops::FilterType::CatmullRom);
         for marker_point in marker_points {
-            let image = image.resize(32, 32, image::imageops::FilterType::CatmullRom);
             image::imageops::overlay(
                 map,
                 &marker_bg,
-                marker_point.x as i64 - 16,
-                marker_point.y as i64 - 32,
+                marker_point.x as i64 - half,
+                marker_point.y as i64 - full,
             );
             image::imageops::overlay(
                 map,
                 &image,
-                marker_point.x as i64 - 16,
-                marker_point.y as i64 - 32,
+                marker_point.x as i64 - half,
+                marker_point.y as i64 - full,
             );
         }
     }
+    Ok(())
 }
 
+#[derive(Clone)]
 pub struct MapGenerator {
-    client: ApiClient,
+    /// shared between generators made with `restyled`, so they share the caches too.
+    client: Arc<ApiClient>,
+    options: RenderOptions,
     // maker_data: MarkerData,
 }
 
+impl Default for MapGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// builds a `MapGenerator` for embedding in other apps, see `MapGenerator::builder`.
+///
+/// ```no_run
+/// # use genshin_map_generator::{MapGenerator, api::client::CachePolicy};
+/// let generator = MapGenerator::builder()
+///     .cache_dir("/var/cache/genshin-maps")
+///     .cache_policy(CachePolicy::OfflineOnly)
+///     .max_concurrent_requests(4)
+///     .progress(|progress| println!("{progress:?}"))
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct MapGeneratorBuilder {
+    client: Option<Arc<ApiClient>>,
+    api: ApiClientBuilder,
+    options: RenderOptions,
+}
+
+impl MapGeneratorBuilder {
+    /// uses the given client, to share it (and its caches) with other generators.
+    /// the cache, request and progress settings of the builder are ignored then,
+    /// the client's own are used.
+    pub fn client(mut self, client: Arc<ApiClient>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// the reqwest client to send requests with, see `ApiClientBuilder::http_client`
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.api = self.api.http_client(client);
+        self
+    }
+
+    /// where responses are cached, `./http-cacache` by default.
+    pub fn cache_dir(mut self, cache_dir: impl Into<std::path::PathBuf>) -> Self {
+        self.api = self.api.cache_dir(cache_dir);
+        self
+    }
+
+    pub fn cache_policy(mut self, cache_policy: CachePolicy) -> Self {
+        self.api = self.api.cache_policy(cache_policy);
+        self
+    }
+
+    /// how many requests can be in flight at once, 8 by default.
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.api = self.api.max_concurrent_requests(max_concurrent_requests);
+        self
+    }
+
+    /// called with the progress of every map generated, see `Progress`
+    pub fn progress(mut self, progress: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        let progress: ProgressHook = Arc::new(progress);
+        self.api = self.api.progress(progress);
+        self
+    }
+
+    /// the options used by all the generated maps.
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn build(self) -> MapGenerator {
+        MapGenerator {
+            client: self.client.unwrap_or_else(|| Arc::new(self.api.build())),
+            options: self.options,
+        }
+    }
+}
+
 impl MapGenerator {
     pub fn new() -> Self {
-        let client = ApiClient::new();
-        Self { client }
+        Self::builder().build()
+    }
+
+    /// for a generator with its own client, cache, request limit or progress hook.
+    pub fn builder() -> MapGeneratorBuilder {
+        MapGeneratorBuilder::default()
+    }
+
+    /// the client the generator fetches with.
+    pub fn client(&self) -> &Arc<ApiClient> {
+        &self.client
+    }
+
+    /// sets the options used by all the generated maps.
+    pub fn with_options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// a generator with other options, sharing this one's client and its caches.
+    pub fn restyled(&self, options: RenderOptions) -> Self {
+        Self {
+            client: self.client.clone(),
+            options,
+        }
+    }
+
+    /// the generator for the query (restyled if it has a style) and its labels.
+    fn with_query(&self, query: impl Into<MarkerQuery>) -> (Cow<'_, Self>, Vec<String>) {
+        let query = query.into();
+        let generator = match query.style {
+            Some(style) => Cow::Owned(self.restyled(style)),
+            None => Cow::Borrowed(self),
+        };
+        (generator, query.labels)
     }
 
     /// generates the map for a given region (not a sub region)
     pub async fn gen_region_map(
         &self,
         region_name: &str,
-        desired_marker_labels: Vec<String>,
+        query: impl Into<MarkerQuery>,
     ) -> anyhow::Result<Option<DynamicImage>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_region(region_name).await? else {
+            return Ok(None);
+        };
+
+        let map_chunk = generator.render(&selection, &desired_marker_labels).await?;
+        Ok(Some(map_chunk))
+    }
+
+    pub async fn gen_area_map(
+        &self,
+        region_name: &str,
+        query: impl Into<MarkerQuery>,
+    ) -> anyhow::Result<Option<DynamicImage>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_area(region_name).await? else {
+            return Ok(None);
+        };
+
+        let map_chunk = generator.render(&selection, &desired_marker_labels).await?;
+        Ok(Some(map_chunk))
+    }
+
+    /// plans a collection run over the desired markers of the region (or sub region),
+    /// split into legs starting at waypoints or statues, and draws it on the region's map.
+    /// see `route`
+    pub async fn gen_region_route(
+        &self,
+        region_name: &str,
+        query: impl Into<MarkerQuery>,
+        options: &RouteOptions,
+    ) -> anyhow::Result<Option<(DynamicImage, PlannedRoute)>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_region(region_name).await? else {
+            return Ok(None);
+        };
+        let route = generator
+            .route_selection(&selection, &desired_marker_labels, options)
+            .await?;
+        Ok(Some(route))
+    }
+
+    /// same as `gen_region_route`, over a whole area.
+    pub async fn gen_area_route(
+        &self,
+        area_name: &str,
+        query: impl Into<MarkerQuery>,
+        options: &RouteOptions,
+    ) -> anyhow::Result<Option<(DynamicImage, PlannedRoute)>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_area(area_name).await? else {
+            return Ok(None);
+        };
+        let route = generator
+            .route_selection(&selection, &desired_marker_labels, options)
+            .await?;
+        Ok(Some(route))
+    }
+
+    async fn route_selection(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+        options: &RouteOptions,
+    ) -> anyhow::Result<(DynamicImage, PlannedRoute)> {
+        let route = self.plan_route(selection, desired_marker_labels, options);
+        let map_chunk = self
+            .render_with_route(selection, desired_marker_labels, Some(&route))
+            .await?;
+        Ok((map_chunk, route))
+    }
+
+    /// the route over the markers that are drawn, so not the ones a mask drops.
+    fn plan_route(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+        options: &RouteOptions,
+    ) -> PlannedRoute {
+        route::plan_route_over(
+            self.matched_markers(selection, desired_marker_labels),
+            self.matched_markers(selection, &route::start_labels()),
+            options,
+        )
+    }
+
+    /// compares an old snapshot of the markers (a saved `MarkerData`) with the current ones,
+    /// and draws the changes in the region (or sub region) on its map. see `diff`
+    pub async fn gen_region_diff(
+        &self,
+        region_name: &str,
+        old_marker_data: &MarkerData,
+        query: impl Into<MarkerQuery>,
+        options: &DiffOptions,
+    ) -> anyhow::Result<Option<(DynamicImage, MarkerDiff)>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_region(region_name).await? else {
+            return Ok(None);
+        };
+        let diff = generator
+            .diff_selection(&selection, old_marker_data, &desired_marker_labels, options)
+            .await?;
+        Ok(Some(diff))
+    }
+
+    /// same as `gen_region_diff`, over a whole area.
+    pub async fn gen_area_diff(
+        &self,
+        area_name: &str,
+        old_marker_data: &MarkerData,
+        query: impl Into<MarkerQuery>,
+        options: &DiffOptions,
+    ) -> anyhow::Result<Option<(DynamicImage, MarkerDiff)>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(selection) = generator.select_area(area_name).await? else {
+            return Ok(None);
+        };
+        let diff = generator
+            .diff_selection(&selection, old_marker_data, &desired_marker_labels, options)
+            .await?;
+        Ok(Some(diff))
+    }
+
+    async fn diff_selection(
+        &self,
+        selection: &MapSelection,
+        old_marker_data: &MarkerData,
+        desired_marker_labels: &[String],
+        options: &DiffOptions,
+    ) -> anyhow::Result<(DynamicImage, MarkerDiff)> {
+        let viewport = selection.viewport();
+        let (width, height) = (
+            selection.frame.width() as f32,
+            selection.frame.height() as f32,
+        );
+
+        let drop_masked = self
+            .options
+            .mask
+            .as_ref()
+            .is_some_and(|mask| mask.drop_markers);
+        // only the markers in the frame (and not masked) are counted, so the totals match
+        // what's drawn.
+        let diff = diff::diff_markers_where(
+            old_marker_data,
+            &selection.marker_data,
+            desired_marker_labels,
+            options,
+            |pos| {
+                let pixel = viewport.to_pixel(pos);
+                pixel.x >= 0.0
+                    && pixel.y >= 0.0
+                    && pixel.x < width
+                    && pixel.y < height
+                    && (!drop_masked || selection.in_regions(pos))
+            },
+        );
+
+        let (mut map_chunk, viewport) = self
+            .fetch_output_chunk(selection, self.options.pixel_ratio())
+            .await?;
+        render::diff::draw_diff(&mut map_chunk, &viewport, &diff);
+        if let Some(options) = &self.options.annotations {
+            annotations::annotate(
+                &mut map_chunk,
+                &viewport,
+                &selection.name,
+                selection.data_timestamp().as_deref(),
+                options,
+            );
+        }
+
+        Ok((map_chunk, diff))
+    }
+
+    /// generates an SVG of the given region (or sub region), see `export::svg`
+    pub async fn gen_region_svg(
+        &self,
+        region_name: &str,
+        query: impl Into<MarkerQuery>,
+    ) -> anyhow::Result<Option<String>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(mut selection) = generator.select_region(region_name).await? else {
+            return Ok(None);
+        };
+        if generator.options.point_details {
+            generator
+                .fetch_point_details(&mut selection, &desired_marker_labels)
+                .await;
+        }
+
+        let svg = generator
+            .render_svg(&selection, &desired_marker_labels)
+            .await?;
+        Ok(Some(svg))
+    }
+
+    /// generates an SVG of the given area, see `export::svg`
+    pub async fn gen_area_svg(
+        &self,
+        region_name: &str,
+        query: impl Into<MarkerQuery>,
+    ) -> anyhow::Result<Option<String>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(mut selection) = generator.select_area(region_name).await? else {
+            return Ok(None);
+        };
+        if generator.options.point_details {
+            generator
+                .fetch_point_details(&mut selection, &desired_marker_labels)
+                .await;
+        }
+
+        let svg = generator
+            .render_svg(&selection, &desired_marker_labels)
+            .await?;
+        Ok(Some(svg))
+    }
+
+    /// generates an interactive HTML page of the given region (or sub region),
+    /// see `export::html`
+    pub async fn gen_region_html(
+        &self,
+        region_name: &str,
+        query: impl Into<MarkerQuery>,
+    ) -> anyhow::Result<Option<String>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(mut selection) = generator.select_region(region_name).await? else {
+            return Ok(None);
+        };
+        if generator.options.point_details {
+            generator
+                .fetch_point_details(&mut selection, &desired_marker_labels)
+                .await;
+        }
+
+        let html = generator
+            .render_html(&selection, &desired_marker_labels)
+            .await?;
+        Ok(Some(html))
+    }
+
+    /// generates an interactive HTML page of the given area, see `export::html`
+    pub async fn gen_area_html(
+        &self,
+        area_name: &str,
+        query: impl Into<MarkerQuery>,
+    ) -> anyhow::Result<Option<String>> {
+        let (generator, desired_marker_labels) = self.with_query(query);
+
+        let Some(mut selection) = generator.select_area(area_name).await? else {
+            return Ok(None);
+        };
+        if generator.options.point_details {
+            generator
+                .fetch_point_details(&mut selection, &desired_marker_labels)
+                .await;
+        }
+
+        let html = generator
+            .render_html(&selection, &desired_marker_labels)
+            .await?;
+        Ok(Some(html))
+    }
+
+    /// finds the first region or sub region whose name contains the given name.
+    pub async fn select_region(&self, region_name: &str) -> anyhow::Result<Option<MapSelection>> {
         let map_ids: Vec<u8> = self.client.fetch_map_ids().await?;
 
         let mut region_result: Option<(RegionData, u8)> = None;
@@ -66,6 +520,7 @@ impl MapGenerator {
             let regions = self.client.fetch_regions(map_id).await?;
             for region in regions {
                 if region.name.contains(region_name) {
+                    tracing::debug!(region = %region.name, map_id, "matched a region");
                     // region matched
                     region_result = Some((region, map_id));
                     break 'map_search;
@@ -76,7 +531,7 @@ impl MapGenerator {
                         // sub region dint match try next subregion.
                         continue;
                     }
-                    println!("yes");
+                    tracing::debug!(sub_region = %sub_region.name, map_id, "matched a sub region");
                     // sub region matched, get out and start processing.
                     region_result = Some((sub_region, map_id));
                     break 'map_search;
@@ -89,65 +544,38 @@ impl MapGenerator {
         };
 
         let map_data = self.client.fetch_map_data(map_id).await?;
-        let marker_data = self.client.fetch_marker_data(map_id).await.unwrap();
+        let marker_data = self.client.fetch_marker_data(map_id).await?;
         let frame = region.get_abs_frame(&map_data.origin());
-        let mut map_chunk = self.client.get_map_chunk(&map_data, &frame).await.unwrap();
 
-        let matched_labels: Vec<&Label> = marker_data
-            .labels
-            .iter()
-            .filter(|label| {
-                desired_marker_labels
-                    .iter()
-                    .any(|desired_label| label.name.contains(desired_label))
-            })
-            .collect();
-
-        println!("a");
-
-        let mut matched_markers = vec![];
-
-        for label in matched_labels {
-            let image = self.client.fetch_image(&label.icon).await.unwrap();
-
-            let matched_marker_points = marker_data
-                .markers
-                .iter()
-                .filter(|marker| marker.label_id == label.id)
-                .map(|marker| {
-                    marker
-                        .pos()
-                        .abs_point(map_data.origin())
-                        .translate_axes(frame.top_left())
-                });
-
-            matched_markers.push((image, matched_marker_points));
-        }
-
-        overlay_markers_hd(&mut map_chunk, matched_markers);
-
-        return Ok(Some(map_chunk));
+        Ok(Some(MapSelection {
+            name: region.name,
+            map_id,
+            map_data,
+            marker_data,
+            frame,
+            regions: vec![frame],
+        }))
     }
 
-    pub async fn gen_area_map(
-        &self,
-        region_name: &str,
-        desired_marker_labels: Vec<String>,
-    ) -> anyhow::Result<Option<DynamicImage>> {
-        // ...
+    /// finds the first area (nation) whose name contains the given name.
+    /// the frame covers all the regions of that area.
+    pub async fn select_area(&self, area_name: &str) -> anyhow::Result<Option<MapSelection>> {
         let areas = self.client.fetch_areas().await?;
         for (area_id, area) in (1..).zip(areas.iter()) {
-            if !area.name.contains(region_name) {
+            if !area.name.contains(area_name) {
                 continue;
             }
 
             let regions = self.client.fetch_regions(area.map_id).await?;
             let map_data = self.client.fetch_map_data(area.map_id).await?;
             // collect all matched regions.and calculate the frame.
-            let frame = regions
+            let regions: Vec<Rect> = regions
                 .into_iter()
                 .filter(|region| region.area_id == area_id as u8)
                 .map(|region| region.get_abs_frame(&map_data.origin()))
+                .collect();
+            let frame = regions
+                .iter()
                 // TODO: rect has a rule that lx, ly should always be top left
                 // rx, ry should always be bottom right.
                 // this initial value of accumulator violates it.
@@ -168,60 +596,466 @@ impl MapGenerator {
                     frame
                 });
 
-                println!("generated frame: {:?}", frame);
+            tracing::debug!(area = %area.name, ?frame, "generated the frame of the area");
 
-            
             let marker_data = self.client.fetch_marker_data(area.map_id).await?;
-            let mut map_chunk = self.client.get_map_chunk(&map_data, &frame).await.unwrap();
 
+            return Ok(Some(MapSelection {
+                name: area.name.clone(),
+                map_id: area.map_id,
+                map_data,
+                marker_data,
+                frame,
+                regions,
+            }));
+        }
 
-            // TODO: REMOVE this duplicate code. as both gen functoins use this.
-            // i came across a weird error along the lines of.
-            // hidden type for `xxxx` captures lifetime that does not appear in bounds"
-            // and also some complaint about closure which was very cryptic to understand.
-            // come back here to fix this when you're good with lifetimes and closures.
+        Ok(None)
+    }
 
-            let matched_labels: Vec<&Label> = marker_data
-                .labels
-                .iter()
-                .filter(|label| {
-                    desired_marker_labels
-                        .iter()
-                        .any(|desired_label| label.name.contains(desired_label))
-                })
-                .collect();
+    /// selects the box between the two points (relative to the origin, like marker positions)
+    /// on the given map. the box is clamped to the top left of the map.
+    pub async fn select_bbox(
+        &self,
+        map_id: u8,
+        top_left: Point,
+        bottom_right: Point,
+    ) -> anyhow::Result<MapSelection> {
+        let map_data = self.client.fetch_map_data(map_id).await?;
+        let origin = map_data.origin();
+        let (top_left, bottom_right) = (top_left.abs_point(origin), bottom_right.abs_point(origin));
+        anyhow::ensure!(
+            top_left.x < bottom_right.x && top_left.y < bottom_right.y,
+            "the top left of the box must be above and left of its bottom right"
+        );
+        let frame = Rect::new(
+            top_left.x.max(0.0) as u32,
+            top_left.y.max(0.0) as u32,
+            bottom_right.x.max(0.0) as u32,
+            bottom_right.y.max(0.0) as u32,
+        );
+
+        let marker_data = self.client.fetch_marker_data(map_id).await?;
+        Ok(MapSelection {
+            name: format!("map {map_id}"),
+            map_id,
+            map_data,
+            marker_data,
+            frame,
+            regions: vec![frame],
+        })
+    }
+
+    /// fills in the hint text and screenshot of the desired markers in the selection,
+    /// see `ApiClient::fetch_point_details`
+    pub async fn fetch_point_details(
+        &self,
+        selection: &mut MapSelection,
+        desired_marker_labels: &[String],
+    ) {
+        let ids: HashSet<u64> = self
+            .matched_markers(selection, desired_marker_labels)
+            .into_iter()
+            .flat_map(|(_, markers)| markers.into_iter().filter_map(|marker| marker.id))
+            .collect();
+
+        let markers: Vec<&mut Marker> = selection
+            .marker_data
+            .markers
+            .iter_mut()
+            .filter(|marker| marker.id.is_some_and(|id| ids.contains(&id)))
+            .collect();
+        self.client
+            .fetch_point_details(selection.map_id, markers)
+            .await;
+    }
 
+    /// renders the selected chunk of the map with the desired markers on it,
+    /// and then applies the render options.
+    pub async fn render(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<DynamicImage> {
+        self.render_with_route(selection, desired_marker_labels, None)
+            .await
+    }
+
+    /// same as `render`, with the route drawn over the markers.
+    pub async fn render_with_route(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+        route: Option<&PlannedRoute>,
+    ) -> anyhow::Result<DynamicImage> {
+        let span = tracing::info_span!("render", name = %selection.name, map_id = selection.map_id);
+        self.render_in_span(selection, desired_marker_labels, route)
+            .instrument(span)
+            .await
+    }
+
+    async fn render_in_span(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+        route: Option<&PlannedRoute>,
+    ) -> anyhow::Result<DynamicImage> {
+        let (mut map_chunk, viewport) = self
+            .fetch_output_chunk(selection, self.options.pixel_ratio())
+            .await?;
+
+        if let Some(options) = &self.options.mask {
+            mask::apply_mask(&mut map_chunk, &viewport, &selection.regions, options);
+        }
+
+        if let Some(options) = &self.options.boundaries {
+            let boundaries = self.fetch_boundaries(selection.map_id).await?;
+            boundaries::draw_boundaries(&mut map_chunk, &viewport, &boundaries, options);
+        }
+
+        if let Some(options) = &self.options.heatmap {
+            let points: Vec<Point> = self
+                .matched_markers(selection, desired_marker_labels)
+                .into_iter()
+                .flat_map(|(_, markers)| markers.into_iter().map(|marker| marker.pos()))
+                .collect();
+            heatmap::draw_heatmap(&mut map_chunk, &viewport, &points, options);
+        } else {
             let mut matched_markers = vec![];
+            let mut drawn = 0;
 
-            for label in matched_labels {
-                let image = self.client.fetch_image(&label.icon).await.unwrap();
+            for (label, markers) in self.matched_markers(selection, desired_marker_labels) {
+                let image = self.fetch_icon(label).await?;
 
-                let matched_marker_points = marker_data
-                    .markers
+                let matched_marker_points: Vec<Point> = markers
                     .iter()
-                    .filter(|marker| marker.label_id == label.id)
-                    .map(|marker| {
-                        marker
-                            .pos()
-                            .abs_point(map_data.origin())
-                            .translate_axes(frame.top_left())
-                    });
-
-                matched_markers.push((image, matched_marker_points));
+                    .map(|marker| viewport.to_pixel(marker.pos()))
+                    .collect();
+
+                drawn += matched_marker_points.len();
+                matched_markers.push((image, matched_marker_points.into_iter()));
             }
 
-            overlay_markers_hd(&mut map_chunk, matched_markers);
+            overlay_markers_sized(&mut map_chunk, matched_markers, self.pin_size())?;
+            tracing::debug!(count = drawn, "drew the markers");
+            self.client.report(Progress::MarkersDrawn { count: drawn });
+        }
 
-            return Ok(Some(map_chunk));
+        if let Some(route) = route {
+            render::route::draw_route(&mut map_chunk, &viewport, route, self.options.pixel_ratio());
         }
 
-        Ok(None)
+        if let Some(options) = &self.options.annotations {
+            annotations::annotate(
+                &mut map_chunk,
+                &viewport,
+                &selection.name,
+                selection.data_timestamp().as_deref(),
+                options,
+            );
+        }
+
+        Ok(map_chunk)
+    }
+
+    /// renders the selected chunk as an SVG with the base map embedded and vector markers.
+    pub async fn render_svg(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<String> {
+        // the exports are scalable already, hd is for raster renders.
+        let (map_chunk, viewport) = self.fetch_output_chunk(selection, 1.0).await?;
+        let layers = self.marker_layers(selection, desired_marker_labels).await?;
+
+        svg::to_svg(&map_chunk, &viewport, &layers)
+    }
+
+    /// renders the selected chunk as a single offline HTML page, see `export::html`
+    pub async fn render_html(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<String> {
+        // the exports are scalable already, hd is for raster renders.
+        let (map_chunk, viewport) = self.fetch_output_chunk(selection, 1.0).await?;
+        let layers = self.marker_layers(selection, desired_marker_labels).await?;
+
+        html::to_html(&selection.name, &map_chunk, &viewport, &layers)
+    }
+
+    /// fetches the icon of the label.
+    async fn fetch_icon(&self, label: &Label) -> anyhow::Result<DynamicImage> {
+        let icon = self
+            .client
+            .fetch_image(&label.icon)
+            .instrument(tracing::debug_span!("icon", label = %label.name))
+            .await?;
+        self.client.report(Progress::IconFetched {
+            label: label.name.clone(),
+        });
+        Ok(icon)
+    }
+
+    /// the desired markers in the selection along with the icons of their labels.
+    async fn marker_layers<'a>(
+        &self,
+        selection: &'a MapSelection,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<Vec<MarkerLayer<'a>>> {
+        let mut layers = vec![];
+        for (label, markers) in self.matched_markers(selection, desired_marker_labels) {
+            let icon = self.fetch_icon(label).await?;
+            layers.push(MarkerLayer {
+                label,
+                icon,
+                markers,
+            });
+        }
+        Ok(layers)
+    }
+
+    /// exports the whole map (not just a region) as a slippy-map tile pyramid,
+    /// with the desired markers baked in. see `export::tiles`
+    pub async fn gen_tiles(
+        &self,
+        map_id: u8,
+        desired_marker_labels: &[String],
+        options: &TileOptions,
+        out_dir: impl AsRef<Path>,
+    ) -> anyhow::Result<TileMetadata> {
+        let map_data = self.client.fetch_map_data(map_id).await?;
+        let (markers, labels) = self
+            .tile_markers(map_id, &map_data, desired_marker_labels)
+            .await?;
+
+        tiles::export_tiles(
+            &self.client,
+            map_id,
+            &map_data,
+            &markers,
+            labels,
+            options,
+            out_dir.as_ref(),
+        )
+        .await
+    }
+
+    /// what `render_tile` needs to render the tiles of the map with the desired markers.
+    /// fetch it once and render any number of tiles from it.
+    pub async fn tile_source(
+        &self,
+        map_id: u8,
+        desired_marker_labels: &[String],
+        options: &TileOptions,
+    ) -> anyhow::Result<TileSource> {
+        let map_data = self.client.fetch_map_data(map_id).await?;
+        let layout = TileLayout::new(&self.client, &map_data, options.tile_size).await?;
+        let (markers, labels) = self
+            .tile_markers(map_id, &map_data, desired_marker_labels)
+            .await?;
+        Ok(TileSource {
+            map_id,
+            map_data,
+            layout,
+            markers,
+            labels,
+        })
+    }
+
+    /// renders a single XYZ tile of the pyramid `gen_tiles` exports, `None` if it's off the
+    /// map. see `export::tiles::render_tile`
+    pub async fn render_tile(
+        &self,
+        source: &TileSource,
+        zoom: u32,
+        x: u32,
+        y: u32,
+    ) -> anyhow::Result<Option<DynamicImage>> {
+        tiles::render_tile(&self.client, source, zoom, x, y).await
+    }
+
+    /// the desired markers at absolute positions, with the names of their labels.
+    async fn tile_markers(
+        &self,
+        map_id: u8,
+        map_data: &MapData,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<(TileMarkers, Vec<String>)> {
+        let marker_data = self.client.fetch_marker_data(map_id).await?;
+        let origin = map_data.origin();
+
+        let index = MarkerIndex::new(&marker_data);
+
+        let mut markers = vec![];
+        let mut labels = vec![];
+        for label in marker_data.matching_labels(desired_marker_labels) {
+            let image = self.fetch_icon(label).await?;
+            let points = index
+                .with_label(label.id)
+                .map(|marker| marker.pos().abs_point(origin))
+                .collect();
+            markers.push((image, points));
+            labels.push(label.name.clone());
+        }
+        Ok((markers, labels))
+    }
+
+    /// renders the selection as a PNG into `writer` a strip at a time, without ever holding
+    /// the whole image, for renders too big for `render`. see `export::strips`
+    /// only the markers are drawn at full resolution, heatmaps, routes, masks, boundaries,
+    /// annotations and `resolution` need the whole image.
+    /// the metadata goes into PNG text chunks, see `RasterMetadata::for_selection`
+    pub async fn render_png_strips(
+        &self,
+        selection: &MapSelection,
+        desired_marker_labels: &[String],
+        strip_height: Option<u32>,
+        metadata: Option<&RasterMetadata>,
+        writer: impl Write,
+    ) -> anyhow::Result<()> {
+        let origin = selection.map_data.origin();
+
+        let mut markers = vec![];
+        for (label, label_markers) in self.matched_markers(selection, desired_marker_labels) {
+            let image = self.fetch_icon(label).await?;
+            let points = label_markers
+                .iter()
+                .map(|marker| marker.pos().abs_point(origin))
+                .collect();
+            markers.push((image, points));
+        }
+
+        strips::write_png_strips(
+            &self.client,
+            &selection.map_data,
+            &selection.frame,
+            &markers,
+            strip_height,
+            metadata,
+            writer,
+        )
+        .await
+    }
+
+    /// GeoJSON of the bounding boxes of all the areas, regions and sub regions of the map.
+    /// for the markers, see `export::geojson::markers_to_geojson`
+    pub async fn gen_regions_geojson(&self, map_id: u8) -> anyhow::Result<serde_json::Value> {
+        let areas = self.client.fetch_areas().await?;
+        let regions = self.client.fetch_regions(map_id).await?;
+
+        // area id is the position in the list, see gen_area_map.
+        let areas: Vec<(u8, &AreaData)> = (1..)
+            .zip(areas.iter())
+            .filter(|(_, area)| area.map_id == map_id)
+            .collect();
+
+        Ok(geojson::regions_to_geojson(&areas, &regions))
+    }
+
+    /// every marker of the desired labels on the map, joined with its label and the region
+    /// and sub region it's in. see `export::table` for writing them as CSV or JSON Lines.
+    /// an empty string matches every label, so `&[String::new()]` dumps everything.
+    pub async fn gen_marker_rows(
+        &self,
+        map_id: u8,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<Vec<MarkerRow>> {
+        let marker_data = self.client.fetch_marker_data(map_id).await?;
+        let regions = self.client.fetch_regions(map_id).await?;
+        let labels = marker_data.matching_labels(desired_marker_labels);
+
+        Ok(table::marker_rows(&marker_data, &labels, &regions))
+    }
+
+    /// how many markers of the desired labels each area, region and sub region of the map has.
+    /// see `export::stats` for the Markdown, JSON and bar chart outputs.
+    pub async fn gen_stats(
+        &self,
+        map_id: u8,
+        desired_marker_labels: &[String],
+    ) -> anyhow::Result<StatsReport> {
+        let rows = self.gen_marker_rows(map_id, desired_marker_labels).await?;
+        // area id is the position in the list, see gen_area_map.
+        let area_names = (1..)
+            .zip(self.client.fetch_areas().await?)
+            .map(|(id, area)| (id, area.name))
+            .collect();
+
+        Ok(StatsReport::from_rows(map_id, &rows, &area_names))
+    }
+
+    /// the map chunk of the selection resized to the output resolution, times the pixel ratio,
+    /// with its viewport.
+    async fn fetch_output_chunk(
+        &self,
+        selection: &MapSelection,
+        pixel_ratio: f32,
+    ) -> anyhow::Result<(DynamicImage, Viewport)> {
+        let map_chunk = self.fetch_map_chunk(selection).await?;
+        let mut viewport = selection.viewport();
+        viewport.scale = self.options.resolution.as_ref().map_or(1.0, |resolution| {
+            resolution.scale_for(map_chunk.width(), map_chunk.height())
+        }) * pixel_ratio;
+        if viewport.scale == 1.0 {
+            return Ok((map_chunk, viewport));
+        }
+
+        tracing::debug!(scale = viewport.scale, "resizing the map chunk");
+        Ok((render::resize_map(&map_chunk, viewport.scale), viewport))
+    }
+
+    /// the selection's markers of the desired labels, without the ones outside its regions
+    /// when masking.
+    fn matched_markers<'a>(
+        &self,
+        selection: &'a MapSelection,
+        desired_marker_labels: &[String],
+    ) -> Vec<(&'a Label, Vec<&'a Marker>)> {
+        let mut matched = selection.matched_markers(desired_marker_labels);
+        if self
+            .options
+            .mask
+            .as_ref()
+            .is_some_and(|mask| mask.drop_markers)
+        {
+            for (_, markers) in &mut matched {
+                markers.retain(|marker| selection.in_regions(marker.pos()));
+            }
+        }
+        matched
+    }
+
+    /// the areas and regions of the map, to outline.
+    async fn fetch_boundaries(&self, map_id: u8) -> anyhow::Result<Vec<Boundary>> {
+        let areas = self.client.fetch_areas().await?;
+        let regions = self.client.fetch_regions(map_id).await?;
+        let mut boundaries = Boundary::from_areas(&areas, map_id);
+        boundaries.extend(Boundary::from_regions(&regions));
+        Ok(boundaries)
+    }
+
+    /// size of the marker pins in the raster renders.
+    fn pin_size(&self) -> u32 {
+        (PIN_SIZE as f32 * self.options.pixel_ratio()) as u32
+    }
+
+    async fn fetch_map_chunk(&self, selection: &MapSelection) -> anyhow::Result<DynamicImage> {
+        let span = tracing::info_span!("map_chunk", name = %selection.name);
+        self.client
+            .get_map_chunk(&selection.map_data, &selection.frame)
+            .instrument(span)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("could not fetch the map chunk of {}", selection.name))
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::{Arc, Mutex};
+
     use super::MapGenerator;
+    use crate::progress::Progress;
 
     #[test]
     fn test_gen_region_map() {
@@ -244,4 +1078,113 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_builder_progress() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let generator = MapGenerator::builder()
+            .cache_dir(std::env::temp_dir().join("genshin-map-builder-test"))
+            .progress({
+                let events = events.clone();
+                move |progress| events.lock().unwrap().push(progress.clone())
+            })
+            .build();
+
+        generator
+            .client()
+            .report(Progress::MarkersDrawn { count: 3 });
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![Progress::MarkersDrawn { count: 3 }]
+        );
+
+        // generators sharing the client report to the same hook.
+        let shared = MapGenerator::builder()
+            .client(generator.client().clone())
+            .build();
+        shared
+            .client()
+            .report(Progress::TileFetched { done: 1, total: 2 });
+        assert_eq!(events.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_overlay_markers_sized() {
+        use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+        use super::overlay_markers_sized;
+        use crate::shapes::point::Point;
+
+        let icon = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 255])));
+        let mut map = DynamicImage::new_rgba8(200, 200);
+        overlay_markers_sized(
+            &mut map,
+            vec![(icon, vec![Point::new(100.0, 100.0)].into_iter())],
+            64,
+        )
+        .unwrap();
+
+        // the icon covers the whole 64px pin, which stands on the point.
+        assert_eq!(map.get_pixel(70, 40), Rgba([255, 0, 0, 255]));
+        assert_eq!(map.get_pixel(130, 99), Rgba([255, 0, 0, 255]));
+        assert_eq!(map.get_pixel(100, 30), Rgba([0, 0, 0, 0]));
+        assert_eq!(map.get_pixel(100, 101), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_masked_route() {
+        use crate::render::{mask::Mask, RenderOptions};
+        use crate::route::RouteOptions;
+        use crate::selection::MapSelection;
+        use crate::shapes::{point::Point, rect::Rect};
+
+        // an area of two regions, with a flower and a waypoint of the neighbouring area
+        // in the gap between them.
+        let selection = MapSelection {
+            name: "Inazuma".to_string(),
+            map_id: 2,
+            map_data: serde_json::from_str(
+                r#"{"slices": [], "origin": [0, 0], "total_size": [300, 300],
+                    "padding": [0, 0]}"#,
+            )
+            .unwrap(),
+            marker_data: serde_json::from_str(
+                r#"{"point_list": [
+                        {"label_id": 1, "area_id": 1, "x_pos": 50.0, "y_pos": 50.0},
+                        {"label_id": 1, "area_id": 1, "x_pos": 250.0, "y_pos": 250.0},
+                        {"label_id": 1, "area_id": 2, "x_pos": 250.0, "y_pos": 50.0},
+                        {"label_id": 2, "area_id": 1, "x_pos": 60.0, "y_pos": 60.0},
+                        {"label_id": 2, "area_id": 2, "x_pos": 260.0, "y_pos": 50.0}],
+                    "label_list": [
+                        {"name": "Sakura Bloom", "icon": "", "id": 1},
+                        {"name": "Teleport Waypoint", "icon": "", "id": 2}]}"#,
+            )
+            .unwrap(),
+            frame: Rect::new(0, 0, 300, 300),
+            regions: vec![Rect::new(0, 0, 100, 100), Rect::new(200, 200, 300, 300)],
+        };
+        let labels = vec!["Sakura Bloom".to_string()];
+        let generator = MapGenerator::builder()
+            .cache_dir(std::env::temp_dir().join("genshin-map-masked-route-test"))
+            .build();
+
+        let route = generator.plan_route(&selection, &labels, &RouteOptions::default());
+        let stops: usize = route.legs.iter().map(|leg| leg.stops.len()).sum();
+        assert_eq!(stops, 3);
+
+        let masked = generator.restyled(RenderOptions {
+            mask: Some(Mask::default()),
+            ..Default::default()
+        });
+        let route = masked.plan_route(&selection, &labels, &RouteOptions::default());
+        let stops: Vec<Point> = route
+            .legs
+            .iter()
+            .flat_map(|leg| leg.start.iter().chain(leg.stops.iter()))
+            .map(|stop| stop.pos)
+            .collect();
+        assert_eq!(stops.len(), 3);
+        assert!(!stops.contains(&Point::new(250.0, 50.0)));
+        assert!(!stops.contains(&Point::new(260.0, 50.0)));
+    }
 }
@@ -1,45 +1,237 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
 
+use genshin_map_generator::api::client::CachePolicy;
+use genshin_map_generator::export::raster::{self, RasterFormat, RasterMetadata};
+use genshin_map_generator::preset::{self, MarkerQuery, Preset};
+use genshin_map_generator::progress::Progress;
+use genshin_map_generator::render::{mask::Mask, Resolution};
+use genshin_map_generator::MapGenerator;
 
+const USAGE: &str = "usage: genshin_map_generator [--region NAME | --area NAME] \
+[--labels A,B | --preset NAME | --preset-file PATH] [--output PATH] \
+[--format png|jpeg[:QUALITY]|webp[:QUALITY|lossless]] [--no-metadata] \
+[--scale FACTOR] [--width PIXELS] [--height PIXELS] [--hd] [--boundaries] [--mask dim|blank] [--stream] \
+[--cache-dir DIR] [--cache default|offline|refresh|no-store] [--list-presets]";
 
+enum Target {
+    Region(String),
+    Area(String),
+}
 
-use image::{DynamicImage, GenericImageView};
-use genshin_map_generator::MapGenerator;
-use genshin_map_generator::shapes::point::Point;
-
-
-/// overlay the given image (map) with a list of images at given coords.
-/// Teyvat Interactive Map API calls these markers "Points"
-pub fn overlay_markers_hd(
-    map: &DynamicImage,
-    marker_entries: Vec<(&DynamicImage, Point)>,
-) -> DynamicImage {
-    // resize the map
-    let (map_w, map_h) = map.dimensions();
-    let mut map = map.resize(map_w * 2, map_h * 2, image::imageops::FilterType::Nearest);
-
-    // overlay all points.
-    for marker_entry in marker_entries {
-        let (marker, point) = marker_entry;
-        let marker = marker.resize(32, 32, image::imageops::FilterType::Nearest);
-        image::imageops::overlay(&mut map, &marker, point.x as i64, point.y as i64);
-    }
-    map
+/// progress on stderr, the tiles as a bar redrawn in place.
+fn print_progress(progress: &Progress) {
+    match progress {
+        Progress::TileFetched { done, total } if done < total => eprint!("\r{progress}"),
+        Progress::TileFetched { .. } => eprintln!("\r{progress}"),
+        _ => eprintln!("{progress}"),
+    }
 }
 
-fn main() {
-    let rt = tokio::runtime::Runtime::new().unwrap();
-    
-    rt.block_on(async {
-        let map_generator = MapGenerator::new();
-        let image = map_generator.gen_area_map("Sumeru", vec![String::from("Teleport Waypoint"), String::from("Magical Crystal Chunk")]).await.unwrap();
-        match image {
-            Some(image) => { image.save("done.jpg").unwrap(); }
-            None => {
-                println!("no matches");
+fn presets_dir() -> anyhow::Result<PathBuf> {
+    preset::presets_dir()
+        .ok_or_else(|| anyhow::anyhow!("can't find the config directory, set GENSHIN_MAP_PRESETS"))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut target = Target::Area(String::from("Sumeru"));
+    let mut labels: Option<Vec<String>> = None;
+    let mut preset: Option<Preset> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut builder = MapGenerator::builder().progress(print_progress);
+    // render png in strips, for maps too big to hold in memory.
+    let mut stream = false;
+    let mut format: Option<RasterFormat> = None;
+    let mut no_metadata = false;
+    let mut resolution: Option<Resolution> = None;
+    let mut hd = false;
+    let mut boundaries = false;
+    let mut mask: Option<Mask> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--list-presets" {
+            let dir = presets_dir()?;
+            for preset in preset::load_presets(&dir)? {
+                println!("{}: {}", preset.name, preset.labels.join(", "));
+            }
+            return Ok(());
+        }
+        if arg == "--stream" {
+            stream = true;
+            continue;
+        }
+        if arg == "--hd" {
+            hd = true;
+            continue;
+        }
+        if arg == "--boundaries" {
+            boundaries = true;
+            continue;
+        }
+        if arg == "--no-metadata" {
+            no_metadata = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{arg} needs a value\n{USAGE}"))?;
+        match arg.as_str() {
+            "--region" => target = Target::Region(value),
+            "--area" => target = Target::Area(value),
+            "--labels" => {
+                labels = Some(
+                    value
+                        .split(',')
+                        .map(|label| label.trim().to_string())
+                        .collect(),
+                )
+            }
+            "--preset" => preset = Some(preset::find_preset(presets_dir()?, &value)?),
+            "--preset-file" => preset = Some(Preset::load(&value)?),
+            "--output" => output = Some(PathBuf::from(value)),
+            "--format" => {
+                if value.to_ascii_lowercase().starts_with("avif") {
+                    anyhow::bail!(raster::AVIF_UNSUPPORTED);
+                }
+                format = Some(RasterFormat::parse(&value).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "unknown format {value}, use png, jpeg[:QUALITY] or webp\n{USAGE}"
+                    )
+                })?)
+            }
+            "--scale" => {
+                resolution.get_or_insert_with(Resolution::default).scale = Some(value.parse()?)
+            }
+            "--width" => {
+                resolution.get_or_insert_with(Resolution::default).width = Some(value.parse()?)
             }
+            "--height" => {
+                resolution.get_or_insert_with(Resolution::default).height = Some(value.parse()?)
+            }
+            "--mask" => {
+                mask = Some(Mask {
+                    blank: match value.as_str() {
+                        "dim" => false,
+                        "blank" => true,
+                        _ => anyhow::bail!("unknown mask {value}, use dim or blank\n{USAGE}"),
+                    },
+                    ..Default::default()
+                })
+            }
+            "--cache-dir" => builder = builder.cache_dir(value),
+            "--cache" => {
+                let policy = CachePolicy::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("unknown cache policy {value}\n{USAGE}"))?;
+                builder = builder.cache_policy(policy);
+            }
+            _ => anyhow::bail!("unknown argument {arg}\n{USAGE}"),
         }
-    });
+    }
 
-}
+    // the labels given on the command line replace the preset's.
+    let output = output
+        .or_else(|| preset.as_ref().and_then(|preset| preset.output.clone()))
+        .unwrap_or_else(|| PathBuf::from("done.jpg"));
+    let mut query = match (preset, labels) {
+        (Some(preset), Some(labels)) => MarkerQuery {
+            labels,
+            ..preset.into()
+        },
+        (Some(preset), None) => preset.into(),
+        (None, Some(labels)) => labels.into(),
+        (None, None) => vec![
+            String::from("Teleport Waypoint"),
+            String::from("Magical Crystal Chunk"),
+        ]
+        .into(),
+    };
+    let extension = output
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    // images are encoded as --format says, or as the extension says.
+    let format = match format {
+        Some(format) => Some(format),
+        None if matches!(extension.as_str(), "svg" | "html") => None,
+        None => Some(RasterFormat::parse(&extension).ok_or_else(|| {
+            anyhow::anyhow!("unknown output format '{extension}', set --format\n{USAGE}")
+        })?),
+    };
+    if stream && format != Some(RasterFormat::Png) {
+        anyhow::bail!("--stream only writes png");
+    }
+    // the resolution given on the command line replaces the preset's.
+    if let Some(resolution) = resolution {
+        query.style.get_or_insert_with(Default::default).resolution = Some(resolution);
+    }
+    if hd {
+        query.style.get_or_insert_with(Default::default).hd = true;
+    }
+    if boundaries {
+        query.style.get_or_insert_with(Default::default).boundaries = Some(Default::default());
+    }
+    if let Some(mask) = mask {
+        query.style.get_or_insert_with(Default::default).mask = Some(mask);
+    }
+    // strips are the plain map and its pins, nothing is resized or drawn on top of them.
+    if let Some(style) = query.style.as_ref().filter(|_| stream) {
+        if style.hd
+            || style.resolution.is_some()
+            || style.mask.is_some()
+            || style.boundaries.is_some()
+            || style.annotations.is_some()
+            || style.heatmap.is_some()
+        {
+            anyhow::bail!(
+                "--stream can't be used with --hd, --scale, --width, --height, --mask, \
+                 --boundaries or a styled preset"
+            );
+        }
+    }
+    if let Some(style) = &query.style {
+        builder = builder.options(style.clone());
+    }
 
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let map_generator = builder.build();
+        let Some(format) = format else {
+            // svg or html.
+            let document = match (&target, extension.as_str()) {
+                (Target::Region(name), "svg") => map_generator.gen_region_svg(name, query).await?,
+                (Target::Area(name), "svg") => map_generator.gen_area_svg(name, query).await?,
+                (Target::Region(name), _) => map_generator.gen_region_html(name, query).await?,
+                (Target::Area(name), _) => map_generator.gen_area_html(name, query).await?,
+            };
+            match document {
+                Some(document) => std::fs::write(&output, document)?,
+                None => println!("no matches"),
+            }
+            return Ok(());
+        };
 
+        let selection = match &target {
+            Target::Region(name) => map_generator.select_region(name).await?,
+            Target::Area(name) => map_generator.select_area(name).await?,
+        };
+        let Some(selection) = selection else {
+            println!("no matches");
+            return Ok(());
+        };
+        let metadata = RasterMetadata::for_selection(&selection, &query.labels);
+        let metadata = (!no_metadata).then_some(&metadata);
+        if stream {
+            let file = BufWriter::new(File::create(&output)?);
+            map_generator
+                .render_png_strips(&selection, &query.labels, None, metadata, file)
+                .await?;
+        } else {
+            let image = map_generator.render(&selection, &query.labels).await?;
+            raster::save(&image, format, metadata, &output)?;
+        }
+        Ok(())
+    })
+}
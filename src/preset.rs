@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::RenderOptions;
+
+/// what to put on a map: the desired labels, and the style to render them with.
+/// `MapGenerator`'s `gen_*` methods take anything that converts into one, so either
+/// a plain `Vec<String>` of labels (rendered with the generator's options) or a `Preset`.
+#[derive(Debug, Clone, Default)]
+pub struct MarkerQuery {
+    pub labels: Vec<String>,
+    /// `None` renders with the generator's options.
+    pub style: Option<RenderOptions>,
+}
+
+impl From<Vec<String>> for MarkerQuery {
+    fn from(labels: Vec<String>) -> Self {
+        Self {
+            labels,
+            style: None,
+        }
+    }
+}
+
+impl From<Preset> for MarkerQuery {
+    fn from(preset: Preset) -> Self {
+        Self {
+            labels: preset.labels,
+            style: Some(preset.style),
+        }
+    }
+}
+
+impl From<&Preset> for MarkerQuery {
+    fn from(preset: &Preset) -> Self {
+        preset.clone().into()
+    }
+}
+
+/// a named, reusable bundle of labels, style and output settings, like "all-oculi".
+///
+/// presets are TOML (or JSON) files in the presets directory (see `presets_dir`),
+/// named after the preset:
+///
+/// ```toml
+/// # all-oculi.toml
+/// labels = ["Anemoculus", "Geoculus", "Electroculus", "Dendroculus"]
+/// output = "oculi.png"
+///
+/// [style.heatmap]
+/// radius = 200
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    /// the file name (without the extension) if not set.
+    pub name: String,
+    pub labels: Vec<String>,
+    pub style: RenderOptions,
+    /// where to save the output by default. the format is picked by the extension.
+    pub output: Option<PathBuf>,
+}
+
+impl Preset {
+    /// reads the preset file, TOML or JSON by its extension.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let preset = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(anyhow::Error::from),
+            _ => toml::from_str(&text).map_err(anyhow::Error::from),
+        };
+        let mut preset: Preset =
+            preset.map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+
+        if preset.name.is_empty() {
+            preset.name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        Ok(preset)
+    }
+}
+
+/// where the user's presets are: `$GENSHIN_MAP_PRESETS` if set, else
+/// `genshin_map_generator/presets` in the user's config directory
+/// (`$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%`).
+pub fn presets_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("GENSHIN_MAP_PRESETS") {
+        return Some(PathBuf::from(dir));
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(config_dir.join("genshin_map_generator").join("presets"))
+}
+
+fn is_preset_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("toml" | "json")
+    )
+}
+
+/// every preset in the directory, sorted by name. a missing directory has no presets.
+pub fn load_presets(dir: impl AsRef<Path>) -> anyhow::Result<Vec<Preset>> {
+    let dir = dir.as_ref();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut presets = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_preset_file(&path) {
+            presets.push(Preset::load(&path)?);
+        }
+    }
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(presets)
+}
+
+/// the preset with the given name from the directory.
+pub fn find_preset(dir: impl AsRef<Path>, name: &str) -> anyhow::Result<Preset> {
+    let dir = dir.as_ref();
+    load_presets(dir)?
+        .into_iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| anyhow::anyhow!("no preset named {name} in {}", dir.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_presets() {
+        let dir = std::env::temp_dir().join(format!("presets-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("all-oculi.toml"),
+            "labels = [\"Anemoculus\", \"Geoculus\"]\n[style.heatmap]\nradius = 200",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("chests.json"),
+            r#"{"name": "every-chest", "labels": ["Chest"], "output": "chests.png"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not a preset").unwrap();
+
+        let presets = load_presets(&dir).unwrap();
+        let names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+        assert_eq!(names, vec!["all-oculi", "every-chest"]);
+        assert_eq!(presets[0].style.heatmap.as_ref().unwrap().radius, 200.0);
+
+        let preset = find_preset(&dir, "every-chest").unwrap();
+        assert_eq!(preset.output, Some(PathBuf::from("chests.png")));
+        assert!(find_preset(&dir, "missing").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_marker_query() {
+        let query = MarkerQuery::from(vec!["Chest".to_string()]);
+        assert!(query.style.is_none());
+
+        let preset = Preset {
+            labels: vec!["Chest".to_string()],
+            ..Default::default()
+        };
+        let query = MarkerQuery::from(&preset);
+        assert_eq!(query.labels, preset.labels);
+        assert!(query.style.is_some());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// what a generator is doing, reported to the progress hook as it happens.
+/// see `MapGeneratorBuilder::progress`
+///
+/// for logs, the generator also emits `tracing` spans and events, install a subscriber
+/// to see them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Progress {
+    /// a slice of the base map was fetched, `done` of the `total` slices the chunk needs.
+    TileFetched { done: usize, total: usize },
+    /// the icon of a label was fetched.
+    IconFetched { label: String },
+    /// markers were drawn on the map.
+    MarkersDrawn { count: usize },
+}
+
+impl fmt::Display for Progress {
+    /// a line for the terminal, with a bar for the tiles.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::TileFetched { done, total } => {
+                const WIDTH: usize = 20;
+                let filled = (done * WIDTH).checked_div(*total).unwrap_or(WIDTH);
+                write!(
+                    f,
+                    "tiles [{}{}] {done}/{total}",
+                    "#".repeat(filled),
+                    "-".repeat(WIDTH - filled)
+                )
+            }
+            Progress::IconFetched { label } => write!(f, "fetched the icon of {label}"),
+            Progress::MarkersDrawn { count } => write!(f, "drew {count} markers"),
+        }
+    }
+}
+
+/// called with every `Progress` event. it's called from the tasks doing the work,
+/// so keep it quick.
+pub type ProgressHook = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// the events as a stream that any number of listeners can subscribe to, like the
+/// server's `/events`. hand `hook` to the generator and `subscribe` to listen.
+#[derive(Debug, Clone)]
+pub struct ProgressEvents {
+    sender: broadcast::Sender<Progress>,
+}
+
+impl Default for ProgressEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressEvents {
+    pub fn new() -> Self {
+        // slow listeners miss the oldest events instead of holding up the generator.
+        let (sender, _) = broadcast::channel(256);
+        Self { sender }
+    }
+
+    /// the hook that sends to the listeners, for `MapGeneratorBuilder::progress`
+    pub fn hook(&self) -> impl Fn(&Progress) + Send + Sync + 'static {
+        let sender = self.sender.clone();
+        move |progress| {
+            // no listeners is fine.
+            let _ = sender.send(progress.clone());
+        }
+    }
+
+    /// the events from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Progress> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Progress::TileFetched { done: 1, total: 4 }.to_string(),
+            "tiles [#####---------------] 1/4"
+        );
+        assert_eq!(
+            Progress::MarkersDrawn { count: 12 }.to_string(),
+            "drew 12 markers"
+        );
+    }
+
+    #[test]
+    fn test_events() {
+        let events = ProgressEvents::new();
+        let hook = events.hook();
+        // sent before anyone listens.
+        hook(&Progress::MarkersDrawn { count: 1 });
+
+        let mut receiver = events.subscribe();
+        hook(&Progress::IconFetched {
+            label: "Chest".to_string(),
+        });
+        let progress = receiver.try_recv().unwrap();
+        assert_eq!(
+            serde_json::to_value(&progress).unwrap(),
+            serde_json::json!({"event": "icon_fetched", "label": "Chest"})
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+}
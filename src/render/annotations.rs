@@ -0,0 +1,273 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::point::Point;
+
+use super::{draw, font, rgba_mut, Viewport};
+
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BANNER: Rgba<u8> = Rgba([255, 255, 255, 210]);
+const GRID: Rgba<u8> = Rgba([255, 255, 255, 90]);
+
+/// cartographic decorations drawn over a rendered map, for printing guides.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Annotations {
+    /// banner with the region name and the date of the data (see
+    /// `MapSelection::data_timestamp`), or the date of the render if that's not known.
+    pub title: bool,
+    /// scale bar in map units (1 unit = 1 pixel of the full resolution map).
+    pub scale_bar: bool,
+    /// spacing of the co-ordinate grid in map units, relative to the API's origin.
+    /// `None` disables the grid.
+    pub grid_spacing: Option<u32>,
+    pub north_arrow: bool,
+    /// thickness of the frame border in pixels, 0 disables it.
+    pub border: u32,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self {
+            title: true,
+            scale_bar: true,
+            grid_spacing: Some(1000),
+            north_arrow: true,
+            border: 4,
+        }
+    }
+}
+
+/// draws the enabled annotations on the map.
+/// `title` is usually the name of the region or area that was rendered, and
+/// `data_timestamp` the selection's `MapSelection::data_timestamp`.
+pub fn annotate(
+    map: &mut DynamicImage,
+    viewport: &Viewport,
+    title: &str,
+    data_timestamp: Option<&str>,
+    options: &Annotations,
+) {
+    let canvas = rgba_mut(map);
+    let (width, height) = canvas.dimensions();
+    // text gets bigger for bigger maps, so that it's still readable when printed.
+    let text_scale = (width.min(height) / 300).clamp(2, 8);
+    let margin = (text_scale * 4) as i64;
+
+    let banner_height = if options.title {
+        font::text_height(text_scale) + 2 * margin as u32
+    } else {
+        0
+    };
+
+    if let Some(spacing) = options.grid_spacing {
+        draw_grid(canvas, viewport, spacing, text_scale / 2, banner_height);
+    }
+
+    if options.scale_bar {
+        draw_scale_bar(canvas, viewport, text_scale, margin);
+    }
+
+    if options.north_arrow {
+        draw_north_arrow(canvas, text_scale, margin, banner_height as i64);
+    }
+
+    if options.title {
+        let text = match data_timestamp {
+            // the date, without the time.
+            Some(timestamp) => format!(
+                "{} - data of {}",
+                title,
+                timestamp.split(' ').next().unwrap_or(timestamp)
+            ),
+            None => format!("{} - rendered {}", title, today()),
+        };
+        draw::fill_rect(canvas, 0, 0, width, banner_height, BANNER);
+        let x = (width as i64 - font::text_width(&text, text_scale) as i64) / 2;
+        font::draw_text(canvas, x.max(margin), margin, &text, text_scale, BLACK);
+    }
+
+    if options.border > 0 {
+        draw::draw_rect_outline(canvas, 0, 0, width, height, options.border, BLACK);
+    }
+}
+
+/// grid lines at every multiple of `spacing` in the API's co-ordinate space.
+/// x values are labeled along the top edge and y values along the left edge.
+fn draw_grid(
+    canvas: &mut RgbaImage,
+    viewport: &Viewport,
+    spacing: u32,
+    text_scale: u32,
+    top_offset: u32,
+) {
+    let spacing = spacing.max(1) as f32;
+    let text_scale = text_scale.max(1);
+    let (width, height) = canvas.dimensions();
+    let top_left = viewport.to_map(Point::new(0.0, 0.0));
+    let bottom_right = viewport.to_map(Point::new(width as f32, height as f32));
+
+    let mut x = (top_left.x / spacing).ceil() * spacing;
+    while x < bottom_right.x {
+        let px = viewport.to_pixel(Point::new(x, 0.0)).x;
+        draw::fill_rect(canvas, px as i64, 0, 1, height, GRID);
+        let label = format!("{}", x as i64);
+        font::draw_text_outlined(
+            canvas,
+            px as i64 + 3,
+            top_offset as i64 + 3,
+            &label,
+            text_scale,
+            WHITE,
+            BLACK,
+        );
+        x += spacing;
+    }
+
+    let mut y = (top_left.y / spacing).ceil() * spacing;
+    while y < bottom_right.y {
+        let py = viewport.to_pixel(Point::new(0.0, y)).y;
+        draw::fill_rect(canvas, 0, py as i64, width, 1, GRID);
+        if py > (top_offset + font::text_height(text_scale) + 6) as f32 {
+            let label = format!("{}", y as i64);
+            font::draw_text_outlined(canvas, 3, py as i64 + 3, &label, text_scale, WHITE, BLACK);
+        }
+        y += spacing;
+    }
+}
+
+/// scale bar at the bottom left, about a fifth of the image wide.
+fn draw_scale_bar(canvas: &mut RgbaImage, viewport: &Viewport, text_scale: u32, margin: i64) {
+    let (width, height) = canvas.dimensions();
+    let units = nice_length(width as f32 / 5.0 / viewport.scale);
+    let bar_width = (units * viewport.scale).round() as u32;
+    let bar_height = (text_scale * 3).max(6);
+    let label = format!("{} units", units as u32);
+    let label_height = font::text_height(text_scale);
+
+    let box_width = bar_width.max(font::text_width(&label, text_scale)) + 2 * margin as u32;
+    let box_height = bar_height + label_height + 3 * margin as u32;
+    let box_x = margin;
+    let box_y = height as i64 - margin - box_height as i64;
+    draw::fill_rect(canvas, box_x, box_y, box_width, box_height, BANNER);
+
+    font::draw_text(
+        canvas,
+        box_x + margin,
+        box_y + margin,
+        &label,
+        text_scale,
+        BLACK,
+    );
+
+    // alternating black and white segments, like on printed maps.
+    let bar_x = box_x + margin;
+    let bar_y = box_y + 2 * margin + label_height as i64;
+    let segments = 4;
+    for segment in 0..segments {
+        let x0 = bar_x + (bar_width * segment / segments) as i64;
+        let x1 = bar_x + (bar_width * (segment + 1) / segments) as i64;
+        let color = if segment % 2 == 0 { BLACK } else { WHITE };
+        draw::fill_rect(canvas, x0, bar_y, (x1 - x0) as u32, bar_height, color);
+    }
+    draw::draw_rect_outline(canvas, bar_x, bar_y, bar_width, bar_height, 1, BLACK);
+}
+
+/// arrow pointing up at the top right, the in-game map always has north up.
+fn draw_north_arrow(canvas: &mut RgbaImage, text_scale: u32, margin: i64, top_offset: i64) {
+    let size = (text_scale * 8) as f32;
+    let cx = canvas.width() as f32 - margin as f32 - size / 2.0;
+    let top = (top_offset + margin) as f32;
+
+    let outline = [
+        (cx, top),
+        (cx + size / 2.0, top + size),
+        (cx, top + size * 0.7),
+        (cx - size / 2.0, top + size),
+    ];
+    draw::fill_polygon(canvas, &outline, WHITE);
+    // left half filled black, the classic look.
+    draw::fill_polygon(canvas, &[outline[0], outline[2], outline[3]], BLACK);
+
+    let x = cx as i64 - font::text_width("N", text_scale) as i64 / 2;
+    let y = (top + size) as i64 + margin / 2;
+    font::draw_text_outlined(canvas, x, y, "N", text_scale, BLACK, WHITE);
+}
+
+/// rounds down to the nearest 1, 2 or 5 times a power of ten.
+fn nice_length(max: f32) -> f32 {
+    if max < 1.0 {
+        return 1.0;
+    }
+    let magnitude = 10f32.powf(max.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|step| step * magnitude)
+        .find(|length| *length <= max)
+        .unwrap_or(magnitude)
+}
+
+/// today's date (UTC) as yyyy-mm-dd.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// converts days since unix epoch to (year, month, day).
+/// see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::rect::Rect;
+
+    #[test]
+    fn test_nice_length() {
+        assert_eq!(nice_length(730.0), 500.0);
+        assert_eq!(nice_length(300.0), 200.0);
+        assert_eq!(nice_length(1999.0), 1000.0);
+        assert_eq!(nice_length(0.2), 1.0);
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_000), (2022, 1, 8));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let mut map = DynamicImage::new_rgba8(600, 600);
+        let viewport = Viewport::new(Point::new(300.0, 300.0), &Rect::new(0, 0, 600, 600));
+        annotate(
+            &mut map,
+            &viewport,
+            "Mondstadt",
+            Some("2023-03-01 10:00:00"),
+            &Annotations::default(),
+        );
+
+        // border is drawn on the edges.
+        assert_eq!(map.as_rgba8().unwrap().get_pixel(0, 300), &BLACK);
+    }
+}
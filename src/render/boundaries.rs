@@ -0,0 +1,215 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::api::models::{AreaData, RegionData};
+use crate::shapes::point::Point;
+
+use super::{draw, font, rgba_mut, Viewport};
+
+const AREA: Rgba<u8> = Rgba([255, 215, 0, 255]);
+const REGION: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const SUB_REGION: Rgba<u8> = Rgba([135, 206, 250, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// outlines of the areas, regions and sub regions on the map, with their names.
+/// helps to see which regions an area render is made of.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Boundaries {
+    pub areas: bool,
+    pub regions: bool,
+    pub sub_regions: bool,
+    /// the name of every box in its top left corner.
+    pub labels: bool,
+    /// thickness of the region outlines in pixels at the normal resolution. areas are drawn
+    /// thicker and sub regions thinner.
+    pub thickness: u32,
+}
+
+impl Default for Boundaries {
+    fn default() -> Self {
+        Self {
+            areas: true,
+            regions: true,
+            sub_regions: true,
+            labels: true,
+            thickness: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryKind {
+    Area,
+    Region,
+    SubRegion,
+}
+
+/// a named box to outline, relative to the API's origin like the regions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Boundary {
+    pub name: String,
+    pub kind: BoundaryKind,
+    pub top_left: Point,
+    pub bottom_right: Point,
+}
+
+impl Boundary {
+    /// the areas of the map with the given id.
+    pub fn from_areas(areas: &[AreaData], map_id: u8) -> Vec<Self> {
+        areas
+            .iter()
+            .filter(|area| area.map_id == map_id)
+            .map(|area| Self {
+                name: area.name.clone(),
+                kind: BoundaryKind::Area,
+                top_left: Point::new(area.lx, area.ly),
+                bottom_right: Point::new(area.rx, area.ry),
+            })
+            .collect()
+    }
+
+    /// the regions, each followed by its sub regions.
+    pub fn from_regions(regions: &[RegionData]) -> Vec<Self> {
+        let boundary = |region: &RegionData, kind| Self {
+            name: region.name.clone(),
+            kind,
+            top_left: Point::new(region.lx, region.ly),
+            bottom_right: Point::new(region.rx, region.ry),
+        };
+        regions
+            .iter()
+            .flat_map(|region| {
+                std::iter::once(boundary(region, BoundaryKind::Region)).chain(
+                    region
+                        .children
+                        .iter()
+                        .map(move |sub_region| boundary(sub_region, BoundaryKind::SubRegion)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// outlines the enabled kinds of boundaries, sub regions first so that the bigger boxes
+/// stay on top where they overlap.
+pub fn draw_boundaries(
+    map: &mut DynamicImage,
+    viewport: &Viewport,
+    boundaries: &[Boundary],
+    options: &Boundaries,
+) {
+    let canvas = rgba_mut(map);
+    for kind in [
+        BoundaryKind::SubRegion,
+        BoundaryKind::Region,
+        BoundaryKind::Area,
+    ] {
+        let enabled = match kind {
+            BoundaryKind::Area => options.areas,
+            BoundaryKind::Region => options.regions,
+            BoundaryKind::SubRegion => options.sub_regions,
+        };
+        if !enabled {
+            continue;
+        }
+        for boundary in boundaries.iter().filter(|boundary| boundary.kind == kind) {
+            draw_boundary(canvas, viewport, boundary, options);
+        }
+    }
+}
+
+fn draw_boundary(
+    canvas: &mut RgbaImage,
+    viewport: &Viewport,
+    boundary: &Boundary,
+    options: &Boundaries,
+) {
+    let (color, weight, text_scale) = match boundary.kind {
+        BoundaryKind::Area => (AREA, 2.0, 3.0),
+        BoundaryKind::Region => (REGION, 1.0, 2.0),
+        BoundaryKind::SubRegion => (SUB_REGION, 0.5, 1.0),
+    };
+    let top_left = viewport.to_pixel(boundary.top_left);
+    let bottom_right = viewport.to_pixel(boundary.bottom_right);
+    let (width, height) = canvas.dimensions();
+    // nothing of it is in the image.
+    if bottom_right.x < 0.0
+        || bottom_right.y < 0.0
+        || top_left.x > width as f32
+        || top_left.y > height as f32
+    {
+        return;
+    }
+
+    let thickness = (options.thickness as f32 * weight * viewport.scale).max(1.0) as u32;
+    let (box_width, box_height) = (
+        (bottom_right.x - top_left.x).max(0.0) as u32,
+        (bottom_right.y - top_left.y).max(0.0) as u32,
+    );
+    draw::draw_rect_outline(
+        canvas,
+        top_left.x as i64,
+        top_left.y as i64,
+        box_width,
+        box_height,
+        thickness,
+        color,
+    );
+
+    if !options.labels {
+        return;
+    }
+    let text_scale = (text_scale * viewport.scale.max(0.5)).round().max(1.0) as u32;
+    let margin = thickness + 2 * text_scale;
+    // only when the name fits in the box.
+    if font::text_width(&boundary.name, text_scale) + 2 * margin > box_width
+        || font::text_height(text_scale) + 2 * margin > box_height
+    {
+        return;
+    }
+    // kept in the image, for boxes that start outside of it.
+    let x = (top_left.x as i64).max(0) + margin as i64;
+    let y = (top_left.y as i64).max(0) + margin as i64;
+    font::draw_text_outlined(canvas, x, y, &boundary.name, text_scale, color, BLACK);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::rect::Rect;
+
+    #[test]
+    fn test_draw_boundaries() {
+        let mut map = DynamicImage::new_rgba8(200, 200);
+        let viewport = Viewport::new(Point::new(100.0, 100.0), &Rect::new(0, 0, 200, 200));
+        let boundaries = vec![
+            Boundary {
+                name: "Inazuma".to_string(),
+                kind: BoundaryKind::Area,
+                top_left: Point::new(-100.0, -100.0),
+                bottom_right: Point::new(100.0, 100.0),
+            },
+            Boundary {
+                name: "Narukami Island".to_string(),
+                kind: BoundaryKind::Region,
+                top_left: Point::new(-50.0, -50.0),
+                bottom_right: Point::new(50.0, 50.0),
+            },
+        ];
+
+        draw_boundaries(&mut map, &viewport, &boundaries, &Boundaries::default());
+        let canvas = map.as_rgba8().unwrap();
+        assert_eq!(canvas.get_pixel(0, 100), &AREA);
+        assert_eq!(canvas.get_pixel(50, 100), &REGION);
+        assert_eq!(canvas.get_pixel(100, 100)[3], 0);
+
+        let mut map = DynamicImage::new_rgba8(200, 200);
+        let options = Boundaries {
+            areas: false,
+            ..Default::default()
+        };
+        draw_boundaries(&mut map, &viewport, &boundaries, &options);
+        assert_eq!(map.as_rgba8().unwrap().get_pixel(0, 100)[3], 0);
+    }
+}
@@ -0,0 +1,86 @@
+use image::{DynamicImage, Rgba};
+
+use crate::diff::MarkerDiff;
+
+use super::{draw, rgba_mut, Viewport};
+
+const ADDED: Rgba<u8> = Rgba([50, 205, 50, 255]);
+const REMOVED: Rgba<u8> = Rgba([220, 20, 60, 255]);
+const MOVED: Rgba<u8> = Rgba([255, 165, 0, 255]);
+const OUTLINE: Rgba<u8> = Rgba([40, 40, 40, 255]);
+
+/// draws added markers as green dots, removed ones as red dots and moved ones as an arrow
+/// from where they were to where they are now.
+pub fn draw_diff(map: &mut DynamicImage, viewport: &Viewport, diff: &MarkerDiff) {
+    let canvas = rgba_mut(map);
+    let radius = (8.0 * viewport.scale).max(4.0);
+    let thickness = (3.0 * viewport.scale).max(2.0);
+
+    for marker in &diff.moved {
+        let (from, to) = (viewport.to_pixel(marker.from), viewport.to_pixel(marker.to));
+        draw::fill_circle(canvas, from.x, from.y, radius / 2.0, MOVED);
+        draw::draw_line(canvas, (from.x, from.y), (to.x, to.y), thickness, MOVED);
+
+        // arrow head, pointing along the line.
+        let length = from.distance(&to);
+        if length > 0.0 {
+            let (dx, dy) = ((to.x - from.x) / length, (to.y - from.y) / length);
+            let head = radius * 1.5;
+            let base = (to.x - dx * head, to.y - dy * head);
+            draw::fill_polygon(
+                canvas,
+                &[
+                    (to.x, to.y),
+                    (base.0 - dy * head / 2.0, base.1 + dx * head / 2.0),
+                    (base.0 + dy * head / 2.0, base.1 - dx * head / 2.0),
+                ],
+                MOVED,
+            );
+        }
+    }
+
+    for (markers, color) in [(&diff.removed, REMOVED), (&diff.added, ADDED)] {
+        for marker in markers {
+            let pixel = viewport.to_pixel(marker.pos);
+            draw::fill_circle(canvas, pixel.x, pixel.y, radius + 2.0, OUTLINE);
+            draw::fill_circle(canvas, pixel.x, pixel.y, radius, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::{DiffMarker, MovedMarker};
+    use crate::shapes::{point::Point, rect::Rect};
+
+    #[test]
+    fn test_draw_diff() {
+        let mut map = DynamicImage::new_rgba8(100, 100);
+        let viewport = Viewport::new(Point::new(0.0, 0.0), &Rect::new(0, 0, 100, 100));
+        let diff_marker = |x, y| DiffMarker {
+            id: None,
+            label: "Sweet Flower".to_string(),
+            label_id: 1,
+            pos: Point::new(x, y),
+        };
+        let diff = MarkerDiff {
+            added: vec![diff_marker(20.0, 20.0)],
+            removed: vec![diff_marker(80.0, 20.0)],
+            moved: vec![MovedMarker {
+                id: None,
+                label: "Sweet Flower".to_string(),
+                label_id: 1,
+                from: Point::new(10.0, 70.0),
+                to: Point::new(90.0, 70.0),
+            }],
+            unchanged: 0,
+        };
+
+        draw_diff(&mut map, &viewport, &diff);
+        let canvas = map.as_rgba8().unwrap();
+        assert_eq!(canvas.get_pixel(20, 20), &ADDED);
+        assert_eq!(canvas.get_pixel(80, 20), &REMOVED);
+        assert_eq!(canvas.get_pixel(50, 70), &MOVED);
+    }
+}
@@ -0,0 +1,177 @@
+use image::{Rgba, RgbaImage};
+
+/// alpha blends the color over the pixel at (x, y). pixels outside the image are ignored.
+pub fn blend_pixel(image: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let alpha = color[3] as u32;
+    if alpha == 255 {
+        *pixel = color;
+        return;
+    }
+    for channel in 0..3 {
+        pixel[channel] =
+            ((color[channel] as u32 * alpha + pixel[channel] as u32 * (255 - alpha)) / 255) as u8;
+    }
+    pixel[3] = (alpha + pixel[3] as u32 * (255 - alpha) / 255) as u8;
+}
+
+/// fills the rect with top left at (x, y), clipped to the image.
+pub fn fill_rect(image: &mut RgbaImage, x: i64, y: i64, width: u32, height: u32, color: Rgba<u8>) {
+    let x0 = x.max(0);
+    let y0 = y.max(0);
+    let x1 = (x + width as i64).min(image.width() as i64);
+    let y1 = (y + height as i64).min(image.height() as i64);
+    for py in y0..y1 {
+        for px in x0..x1 {
+            blend_pixel(image, px, py, color);
+        }
+    }
+}
+
+/// draws the outline of a rect, the outline grows inwards by `thickness` pixels.
+pub fn draw_rect_outline(
+    image: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    width: u32,
+    height: u32,
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let t = thickness.min(width / 2).min(height / 2).max(1);
+    fill_rect(image, x, y, width, t, color);
+    fill_rect(image, x, y + (height - t) as i64, width, t, color);
+    fill_rect(image, x, y + t as i64, t, height - 2 * t, color);
+    fill_rect(
+        image,
+        x + (width - t) as i64,
+        y + t as i64,
+        t,
+        height - 2 * t,
+        color,
+    );
+}
+
+/// fills a circle centered at (cx, cy).
+pub fn fill_circle(image: &mut RgbaImage, cx: f32, cy: f32, radius: f32, color: Rgba<u8>) {
+    let r2 = radius * radius;
+    let (x0, x1) = ((cx - radius).floor() as i64, (cx + radius).ceil() as i64);
+    let (y0, y1) = ((cy - radius).floor() as i64, (cy + radius).ceil() as i64);
+    for py in y0..=y1 {
+        for px in x0..=x1 {
+            let dx = px as f32 + 0.5 - cx;
+            let dy = py as f32 + 0.5 - cy;
+            if dx * dx + dy * dy <= r2 {
+                blend_pixel(image, px, py, color);
+            }
+        }
+    }
+}
+
+/// draws a line of given thickness between two points.
+/// implemented as a row of circles so that joints of polylines look round.
+pub fn draw_line(
+    image: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let radius = (thickness / 2.0).max(0.5);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    // step half a radius at a time, enough to avoid gaps.
+    let steps = ((length / (radius / 2.0).max(0.5)).ceil() as u32).max(1);
+
+    if color[3] == 255 {
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            fill_circle(image, from.0 + dx * t, from.1 + dy * t, radius, color);
+        }
+        return;
+    }
+
+    // translucent lines can't overdraw circles, otherwise they get darker
+    // where the circles overlap. rasterize by distance to the segment instead.
+    let (x0, x1) = (from.0.min(to.0) - radius, from.0.max(to.0) + radius);
+    let (y0, y1) = (from.1.min(to.1) - radius, from.1.max(to.1) + radius);
+    let length2 = (length * length).max(f32::EPSILON);
+    for py in y0.floor() as i64..=y1.ceil() as i64 {
+        for px in x0.floor() as i64..=x1.ceil() as i64 {
+            let (qx, qy) = (px as f32 + 0.5, py as f32 + 0.5);
+            let t = (((qx - from.0) * dx + (qy - from.1) * dy) / length2).clamp(0.0, 1.0);
+            let (nx, ny) = (from.0 + dx * t - qx, from.1 + dy * t - qy);
+            if nx * nx + ny * ny <= radius * radius {
+                blend_pixel(image, px, py, color);
+            }
+        }
+    }
+}
+
+/// fills a (possibly concave) polygon using even-odd scanline filling.
+pub fn fill_polygon(image: &mut RgbaImage, points: &[(f32, f32)], color: Rgba<u8>) {
+    if points.len() < 3 {
+        return;
+    }
+    let min_y = points
+        .iter()
+        .map(|p| p.1)
+        .fold(f32::MAX, f32::min)
+        .floor()
+        .max(0.0) as i64;
+    let max_y = points
+        .iter()
+        .map(|p| p.1)
+        .fold(f32::MIN, f32::max)
+        .ceil()
+        .min(image.height() as f32) as i64;
+
+    for py in min_y..max_y {
+        let scan_y = py as f32 + 0.5;
+        let mut crossings = vec![];
+        for i in 0..points.len() {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            if (a.1 <= scan_y) != (b.1 <= scan_y) {
+                crossings.push(a.0 + (scan_y - a.1) / (b.1 - a.1) * (b.0 - a.0));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks(2) {
+            if let [start, end] = pair {
+                for px in start.round() as i64..end.round() as i64 {
+                    blend_pixel(image, px, py, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_blend_pixel() {
+        let mut image = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        blend_pixel(&mut image, 0, 0, Rgba([255, 255, 255, 127]));
+        assert_eq!(image.get_pixel(0, 0)[0], 127);
+        assert_eq!(image.get_pixel(0, 0)[3], 255);
+        // out of bounds is ignored.
+        blend_pixel(&mut image, -1, 5, Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_fill_polygon() {
+        let mut image = RgbaImage::new(10, 10);
+        let red = Rgba([255, 0, 0, 255]);
+        fill_polygon(&mut image, &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], red);
+        assert_eq!(image.get_pixel(9, 1), &red);
+        assert_eq!(image.get_pixel(1, 9), &Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,213 @@
+use image::{DynamicImage, Rgba};
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::point::Point;
+
+use super::{draw, rgba_mut, Viewport};
+
+/// colours of the density ramp, from the lowest to the highest density.
+const RAMP: [[u8; 3]; 5] = [
+    [0, 0, 255],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 255, 0],
+    [255, 0, 0],
+];
+
+/// density layer drawn instead of the marker pins, for labels with lots of markers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Heatmap {
+    /// standard deviation of the gaussian kernel in map units.
+    pub radius: f32,
+    /// opacity of the densest spots, from 0 to 1.
+    pub opacity: f32,
+    /// density (in markers under a kernel peak) drawn as the hottest colour.
+    /// `None` uses the densest spot of the map, fix it to compare maps with each other.
+    pub max_density: Option<f32>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self {
+            radius: 150.0,
+            opacity: 0.7,
+            max_density: None,
+        }
+    }
+}
+
+/// a grid of densities, coarser than the image so that big maps stay fast to blur.
+struct DensityGrid {
+    width: usize,
+    height: usize,
+    /// image pixels per grid cell.
+    cell: f32,
+    values: Vec<f32>,
+}
+
+impl DensityGrid {
+    fn new(image_width: u32, image_height: u32, cell: f32) -> Self {
+        let width = (image_width as f32 / cell).ceil() as usize + 1;
+        let height = (image_height as f32 / cell).ceil() as usize + 1;
+        Self {
+            width,
+            height,
+            cell,
+            values: vec![0.0; width * height],
+        }
+    }
+
+    /// adds a point, spread over the 4 cells around it so it doesn't snap to the grid.
+    fn splat(&mut self, pixel: Point) {
+        let (gx, gy) = (pixel.x / self.cell, pixel.y / self.cell);
+        let (x0, y0) = (gx.floor(), gy.floor());
+        let (fx, fy) = (gx - x0, gy - y0);
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let (x, y) = (x0 as i64 + dx, y0 as i64 + dy);
+            if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+                self.values[y as usize * self.width + x as usize] += weight;
+            }
+        }
+    }
+
+    /// separable gaussian blur with the given standard deviation in cells.
+    /// the kernel isn't normalised, so a lone point has a peak of 1.
+    fn blur(&mut self, sigma: f32) {
+        let reach = (3.0 * sigma).ceil() as i64;
+        let kernel: Vec<f32> = (-reach..=reach)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect();
+
+        let (width, height) = (self.width, self.height);
+        let pass = |values: &[f32], along_x: bool| {
+            let mut blurred = vec![0.0; values.len()];
+            for y in 0..height {
+                for x in 0..width {
+                    let value = values[y * width + x];
+                    if value == 0.0 {
+                        continue;
+                    }
+                    for (k, weight) in kernel.iter().enumerate() {
+                        let offset = k as i64 - reach;
+                        let (tx, ty) = if along_x {
+                            (x as i64 + offset, y as i64)
+                        } else {
+                            (x as i64, y as i64 + offset)
+                        };
+                        if tx >= 0 && ty >= 0 && (tx as usize) < width && (ty as usize) < height {
+                            blurred[ty as usize * width + tx as usize] += value * weight;
+                        }
+                    }
+                }
+            }
+            blurred
+        };
+
+        let blurred = pass(&self.values, true);
+        self.values = pass(&blurred, false);
+    }
+
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.values[y.min(self.height - 1) * self.width + x.min(self.width - 1)]
+    }
+
+    /// bilinear sample at an image pixel.
+    fn sample(&self, px: f32, py: f32) -> f32 {
+        let (gx, gy) = (px / self.cell, py / self.cell);
+        let (x0, y0) = (gx.floor() as usize, gy.floor() as usize);
+        let (fx, fy) = (gx.fract(), gy.fract());
+        let top = self.get(x0, y0) * (1.0 - fx) + self.get(x0 + 1, y0) * fx;
+        let bottom = self.get(x0, y0 + 1) * (1.0 - fx) + self.get(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn max(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+/// colour of the ramp at `t` (0 to 1), linearly interpolated between the stops.
+fn ramp_color(t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f32;
+    let i = (t.floor() as usize).min(RAMP.len() - 2);
+    let f = t - i as f32;
+    let (a, b) = (RAMP[i], RAMP[i + 1]);
+    [0, 1, 2].map(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f).round() as u8)
+}
+
+/// blends a gaussian density heatmap of the points (relative to the origin, like markers)
+/// over the map. sparse areas fade out, so the base map stays visible around the clusters.
+pub fn draw_heatmap(
+    map: &mut DynamicImage,
+    viewport: &Viewport,
+    points: &[Point],
+    options: &Heatmap,
+) {
+    let canvas = rgba_mut(map);
+    let (width, height) = canvas.dimensions();
+    if points.is_empty() || width == 0 || height == 0 {
+        return;
+    }
+
+    let sigma = (options.radius * viewport.scale).max(1.0);
+    // ~4 cells per sigma is plenty for a smooth gaussian, and keeps the blur cheap.
+    let mut grid = DensityGrid::new(width, height, (sigma / 4.0).max(1.0));
+    for point in points {
+        grid.splat(viewport.to_pixel(*point));
+    }
+    grid.blur(sigma / grid.cell);
+
+    let max_density = options.max_density.unwrap_or_else(|| grid.max());
+    if max_density <= 0.0 {
+        return;
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let t = grid.sample(x as f32, y as f32) / max_density;
+            // don't tint the whole map blue for a few stray markers.
+            if t < 0.01 {
+                continue;
+            }
+            let [r, g, b] = ramp_color(t);
+            let alpha = (t.sqrt().min(1.0) * options.opacity.clamp(0.0, 1.0) * 255.0).round();
+            draw::blend_pixel(canvas, x as i64, y as i64, Rgba([r, g, b, alpha as u8]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::rect::Rect;
+
+    #[test]
+    fn test_draw_heatmap() {
+        let mut map = DynamicImage::new_rgba8(200, 200);
+        let viewport = Viewport::new(Point::new(0.0, 0.0), &Rect::new(0, 0, 200, 200));
+        let options = Heatmap {
+            radius: 10.0,
+            opacity: 1.0,
+            max_density: None,
+        };
+
+        draw_heatmap(&mut map, &viewport, &[Point::new(50.0, 50.0)], &options);
+        let canvas = map.as_rgba8().unwrap();
+        // hottest at the marker, nothing far away from it.
+        assert_eq!(canvas.get_pixel(50, 50), &Rgba([255, 0, 0, 255]));
+        assert_eq!(canvas.get_pixel(150, 150)[3], 0);
+    }
+
+    #[test]
+    fn test_ramp_color() {
+        assert_eq!(ramp_color(0.0), [0, 0, 255]);
+        assert_eq!(ramp_color(0.5), [0, 255, 0]);
+        assert_eq!(ramp_color(1.0), [255, 0, 0]);
+    }
+}
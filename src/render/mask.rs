@@ -0,0 +1,112 @@
+use image::{DynamicImage, Rgba};
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::rect::Rect;
+
+use super::{rgba_mut, Viewport};
+
+/// hides what's outside the selected regions. an area's frame is the bounding box of its
+/// regions, so it also shows bits of the neighbouring areas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mask {
+    /// makes the outside transparent (black in formats without alpha) instead of dimming it.
+    pub blank: bool,
+    /// how much darker the outside gets, from 0 (not at all) to 255 (black).
+    pub dim: u8,
+    /// leaves out the markers outside the regions.
+    pub drop_markers: bool,
+}
+
+impl Default for Mask {
+    fn default() -> Self {
+        Self {
+            blank: false,
+            dim: 160,
+            drop_markers: true,
+        }
+    }
+}
+
+/// dims or blanks every pixel outside the union of the regions (from the top left of the
+/// map, like `MapSelection::regions`), a row at a time.
+pub fn apply_mask(map: &mut DynamicImage, viewport: &Viewport, regions: &[Rect], options: &Mask) {
+    let canvas = rgba_mut(map);
+    let (width, height) = canvas.dimensions();
+    // the regions in pixels, as (left, top, right, bottom).
+    let to_pixels = |x: u32, y: u32| {
+        (
+            ((x as f32 - viewport.top_left.x) * viewport.scale).round() as i64,
+            ((y as f32 - viewport.top_left.y) * viewport.scale).round() as i64,
+        )
+    };
+    let boxes: Vec<(i64, i64, i64, i64)> = regions
+        .iter()
+        .map(|region| {
+            let (left, top) = to_pixels(region.lx, region.ly);
+            let (right, bottom) = to_pixels(region.rx, region.ry);
+            (left, top, right, bottom)
+        })
+        .collect();
+
+    let outside = |pixel: &mut Rgba<u8>| {
+        if options.blank {
+            *pixel = Rgba([0, 0, 0, 0]);
+        } else {
+            let keep = 255 - options.dim as u32;
+            for channel in &mut pixel.0[..3] {
+                *channel = (*channel as u32 * keep / 255) as u8;
+            }
+        }
+    };
+
+    for y in 0..height {
+        let row = y as i64;
+        let mut spans: Vec<(i64, i64)> = boxes
+            .iter()
+            .filter(|(_, top, _, bottom)| (*top..*bottom).contains(&row))
+            .map(|(left, _, right, _)| (*left, *right))
+            .collect();
+        spans.sort_unstable();
+
+        let mut x = 0;
+        for (left, right) in spans.into_iter().chain([(width as i64, width as i64)]) {
+            let left = left.clamp(0, width as i64);
+            for masked in x..left.max(x) {
+                outside(canvas.get_pixel_mut(masked as u32, y));
+            }
+            x = x.max(right.clamp(0, width as i64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shapes::point::Point;
+
+    #[test]
+    fn test_apply_mask() {
+        let white = Rgba([255, 255, 255, 255]);
+        let mut map = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(100, 100, white));
+        let mut viewport = Viewport::new(Point::new(0.0, 0.0), &Rect::new(100, 100, 300, 300));
+        viewport.scale = 0.5;
+        // an L of two regions.
+        let regions = [Rect::new(100, 100, 200, 300), Rect::new(200, 200, 300, 300)];
+
+        apply_mask(&mut map, &viewport, &regions, &Mask::default());
+        let canvas = map.as_rgba8().unwrap();
+        assert_eq!(canvas.get_pixel(10, 10), &white);
+        assert_eq!(canvas.get_pixel(90, 90), &white);
+        assert_eq!(canvas.get_pixel(90, 10), &Rgba([95, 95, 95, 255]));
+
+        let options = Mask {
+            blank: true,
+            ..Default::default()
+        };
+        apply_mask(&mut map, &viewport, &regions, &options);
+        let canvas = map.as_rgba8().unwrap();
+        assert_eq!(canvas.get_pixel(90, 10), &Rgba([0, 0, 0, 0]));
+        assert_eq!(canvas.get_pixel(49, 10), &white);
+    }
+}
@@ -0,0 +1,199 @@
+pub mod annotations;
+pub mod boundaries;
+pub mod diff;
+pub mod draw;
+pub mod font;
+pub mod heatmap;
+pub mod mask;
+pub mod route;
+
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::shapes::{point::Point, rect::Rect};
+
+use annotations::Annotations;
+use boundaries::Boundaries;
+use heatmap::Heatmap;
+use mask::Mask;
+
+/// options controlling how the generated maps look.
+/// everything is off by default, which renders just the map chunk and the markers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderOptions {
+    /// cartographic decorations (title, scale bar, grid, ...) drawn on top of the map.
+    pub annotations: Option<Annotations>,
+    /// outlines of the areas, regions and sub regions under the markers.
+    pub boundaries: Option<Boundaries>,
+    /// draws a density heatmap of the markers instead of their pins.
+    pub heatmap: Option<Heatmap>,
+    /// dims or blanks what's outside the selected regions, and drops the markers there.
+    pub mask: Option<Mask>,
+    /// fetch the hint text and screenshot of every marker for the exports (SVG, HTML..).
+    /// takes a request per marker the first time, so it's off by default.
+    pub point_details: bool,
+    /// size of the output, the base map is resized to it. icons keep their size.
+    pub resolution: Option<Resolution>,
+    /// renders images (not SVG or HTML) at twice the resolution for print or high-DPI
+    /// screens: the map is upscaled, and icons, lines and text are drawn twice as big.
+    pub hd: bool,
+}
+
+impl RenderOptions {
+    /// output pixels per pixel at the normal resolution, 2 in HD.
+    pub fn pixel_ratio(&self) -> f32 {
+        if self.hd {
+            2.0
+        } else {
+            1.0
+        }
+    }
+}
+
+/// how big the output is. `scale` wins if set, otherwise the output is fit within
+/// `width` and/or `height`, keeping the aspect ratio.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Resolution {
+    /// output pixels per map pixel, like 0.25 for a quarter of the width and height.
+    pub scale: Option<f32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl Resolution {
+    /// output pixels per map pixel for a map chunk of the given size.
+    pub fn scale_for(&self, width: u32, height: u32) -> f32 {
+        let scale = match self.scale {
+            Some(scale) => scale,
+            None => [
+                self.width.map(|w| w as f32 / width.max(1) as f32),
+                self.height.map(|h| h as f32 / height.max(1) as f32),
+            ]
+            .into_iter()
+            .flatten()
+            .reduce(f32::min)
+            .unwrap_or(1.0),
+        };
+        if scale.is_finite() && scale > 0.0 {
+            scale
+        } else {
+            1.0
+        }
+    }
+}
+
+/// resizes the map by the scale with a quality filter, keeping at least a pixel.
+pub fn resize_map(map: &DynamicImage, scale: f32) -> DynamicImage {
+    if scale == 1.0 {
+        return map.clone();
+    }
+    let width = ((map.width() as f32 * scale).round() as u32).max(1);
+    let height = ((map.height() as f32 * scale).round() as u32).max(1);
+    map.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// describes how the pixels of a rendered image relate to the API's co-ordinates.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    /// position of the API's origin from the top left of the map.
+    pub origin: Point,
+    /// position of the top left of the rendered frame from the top left of the map.
+    pub top_left: Point,
+    /// output pixels per map pixel.
+    pub scale: f32,
+}
+
+impl Viewport {
+    pub fn new(origin: Point, frame: &Rect) -> Self {
+        Self {
+            origin,
+            top_left: frame.top_left(),
+            scale: 1.0,
+        }
+    }
+
+    /// converts origin relative co-ordinates (what the API returns for markers, regions etc.,)
+    /// to a pixel position in the rendered image.
+    pub fn to_pixel(&self, pos: Point) -> Point {
+        let pixel = pos.abs_point(self.origin).translate_axes(self.top_left);
+        Point::new(pixel.x * self.scale, pixel.y * self.scale)
+    }
+
+    /// inverse of `to_pixel`
+    pub fn to_map(&self, pixel: Point) -> Point {
+        Point::new(
+            pixel.x / self.scale + self.top_left.x - self.origin.x,
+            pixel.y / self.scale + self.top_left.y - self.origin.y,
+        )
+    }
+}
+
+/// gives mutable access to the pixels of the image, converting it to rgba8 only if needed.
+/// map chunks are already rgba8, so this usually doesn't copy.
+pub fn rgba_mut(image: &mut DynamicImage) -> &mut RgbaImage {
+    if !matches!(image, DynamicImage::ImageRgba8(_)) {
+        *image = DynamicImage::ImageRgba8(image.to_rgba8());
+    }
+    match image {
+        DynamicImage::ImageRgba8(canvas) => canvas,
+        _ => unreachable!("converted above"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_viewport_round_trip() {
+        let mut viewport =
+            Viewport::new(Point::new(1000.0, 500.0), &Rect::new(800, 400, 1600, 900));
+        viewport.scale = 0.5;
+
+        let pixel = viewport.to_pixel(Point::new(0.0, 0.0));
+        assert_eq!(pixel, Point::new(100.0, 50.0));
+        assert_eq!(viewport.to_map(pixel), Point::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_resolution() {
+        let scale = |resolution: Resolution| resolution.scale_for(4000, 2000);
+        assert_eq!(scale(Resolution::default()), 1.0);
+        assert_eq!(
+            scale(Resolution {
+                scale: Some(0.5),
+                width: Some(100),
+                height: None,
+            }),
+            0.5
+        );
+        assert_eq!(
+            scale(Resolution {
+                width: Some(1000),
+                ..Default::default()
+            }),
+            0.25
+        );
+        // fits within both.
+        assert_eq!(
+            scale(Resolution {
+                width: Some(2000),
+                height: Some(200),
+                ..Default::default()
+            }),
+            0.1
+        );
+        assert_eq!(
+            scale(Resolution {
+                scale: Some(0.0),
+                ..Default::default()
+            }),
+            1.0
+        );
+
+        let map = resize_map(&DynamicImage::new_rgba8(400, 200), 0.25);
+        assert_eq!((map.width(), map.height()), (100, 50));
+    }
+}
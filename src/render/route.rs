@@ -0,0 +1,136 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::route::PlannedRoute;
+use crate::shapes::point::Point;
+
+use super::{draw, font, rgba_mut, Viewport};
+
+/// every leg gets the next colour, wrapping around.
+const LEG_COLORS: [Rgba<u8>; 6] = [
+    Rgba([255, 140, 0, 255]),
+    Rgba([30, 144, 255, 255]),
+    Rgba([50, 205, 50, 255]),
+    Rgba([220, 20, 60, 255]),
+    Rgba([186, 85, 211, 255]),
+    Rgba([255, 215, 0, 255]),
+];
+const LINE_OUTLINE: Rgba<u8> = Rgba([40, 40, 40, 255]);
+const BADGE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+pub fn leg_color(leg: usize) -> Rgba<u8> {
+    LEG_COLORS[leg % LEG_COLORS.len()]
+}
+
+/// draws every leg as a polyline from its waypoint through its stops, in its own colour.
+/// stops get a numbered badge under them, waypoints get "T" and the leg's number.
+/// the badges are scaled by `pixel_ratio`, like the pins.
+pub fn draw_route(
+    map: &mut DynamicImage,
+    viewport: &Viewport,
+    route: &PlannedRoute,
+    pixel_ratio: f32,
+) {
+    let canvas = rgba_mut(map);
+    let thickness = (4.0 * viewport.scale).max(3.0);
+
+    let mut number = 1;
+    for (i, leg) in route.legs.iter().enumerate() {
+        let color = leg_color(i);
+        let points: Vec<Point> = leg
+            .start
+            .iter()
+            .chain(leg.stops.iter())
+            .map(|stop| viewport.to_pixel(stop.pos))
+            .collect();
+
+        for (width, color) in [(thickness + 3.0, LINE_OUTLINE), (thickness, color)] {
+            for pair in points.windows(2) {
+                draw::draw_line(
+                    canvas,
+                    (pair[0].x, pair[0].y),
+                    (pair[1].x, pair[1].y),
+                    width,
+                    color,
+                );
+            }
+        }
+
+        let mut points = points.into_iter();
+        if leg.start.is_some() {
+            let waypoint = points.next().unwrap();
+            draw_badge(canvas, waypoint, &format!("T{}", i + 1), color, pixel_ratio);
+        }
+        for point in points {
+            draw_badge(canvas, point, &number.to_string(), color, pixel_ratio);
+            number += 1;
+        }
+    }
+}
+
+/// small circle with text in it, just below the point so it doesn't hide the marker's pin.
+pub fn draw_badge(
+    canvas: &mut RgbaImage,
+    point: Point,
+    text: &str,
+    border: Rgba<u8>,
+    pixel_ratio: f32,
+) {
+    let text_scale = (2.0 * pixel_ratio).round().max(1.0) as u32;
+    let radius = (font::text_width(text, text_scale) as f32 / 2.0 + 5.0 * pixel_ratio)
+        .max(10.0 * pixel_ratio);
+    let center = Point::new(point.x, point.y + radius);
+
+    draw::fill_circle(
+        canvas,
+        center.x,
+        center.y,
+        radius + 2.0 * pixel_ratio,
+        border,
+    );
+    draw::fill_circle(canvas, center.x, center.y, radius, BADGE);
+    font::draw_text(
+        canvas,
+        (center.x - font::text_width(text, text_scale) as f32 / 2.0).round() as i64,
+        (center.y - font::text_height(text_scale) as f32 / 2.0).round() as i64,
+        text,
+        text_scale,
+        BLACK,
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::route::{Leg, RouteStop};
+    use crate::shapes::rect::Rect;
+
+    #[test]
+    fn test_draw_route() {
+        let mut map = DynamicImage::new_rgba8(100, 100);
+        let viewport = Viewport::new(Point::new(0.0, 0.0), &Rect::new(0, 0, 100, 100));
+        let stop = |x, y| RouteStop {
+            label: "Glaze Lily".to_string(),
+            pos: Point::new(x, y),
+        };
+        let route = PlannedRoute {
+            legs: vec![
+                Leg {
+                    start: None,
+                    stops: vec![stop(10.0, 10.0), stop(90.0, 10.0)],
+                    length: 80.0,
+                },
+                Leg {
+                    start: None,
+                    stops: vec![stop(10.0, 60.0), stop(90.0, 60.0)],
+                    length: 80.0,
+                },
+            ],
+        };
+
+        draw_route(&mut map, &viewport, &route, 1.0);
+        // somewhere along the lines, each in its leg's colour.
+        assert_eq!(map.as_rgba8().unwrap().get_pixel(50, 10), &leg_color(0));
+        assert_eq!(map.as_rgba8().unwrap().get_pixel(50, 60), &leg_color(1));
+    }
+}
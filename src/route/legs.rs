@@ -0,0 +1,136 @@
+use std::ops::Range;
+
+use crate::shapes::point::Point;
+
+/// a part of the tour walked after teleporting to a waypoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegSplit {
+    /// index of the waypoint the leg begins at, `None` if there were no waypoints.
+    pub waypoint: Option<usize>,
+    /// positions in the tour's order covered by this leg.
+    pub range: Range<usize>,
+}
+
+/// index of the waypoint closest to the point.
+fn nearest(waypoints: &[Point], to: Point) -> Option<usize> {
+    (0..waypoints.len()).min_by(|a, b| {
+        waypoints[*a]
+            .distance(&to)
+            .total_cmp(&waypoints[*b].distance(&to))
+    })
+}
+
+/// splits a tour (items visited in `order`) into legs, each starting at the waypoint
+/// nearest to its first item. a teleport costs `teleport_cost` map units of walking, so
+/// a new leg is started wherever teleporting ahead is cheaper than walking there.
+///
+/// the split is optimal for the given order: `cost[i]`, the cheapest way to collect the
+/// first `i` items, is the cheapest `cost[j]` plus a teleport to item `j` and a walk to `i`.
+pub fn split_into_legs(
+    items: &[Point],
+    order: &[usize],
+    waypoints: &[Point],
+    teleport_cost: f32,
+) -> Vec<LegSplit> {
+    if order.is_empty() {
+        return vec![];
+    }
+    if waypoints.is_empty() {
+        return vec![LegSplit {
+            waypoint: None,
+            range: 0..order.len(),
+        }];
+    }
+
+    let point = |position: usize| items[order[position]];
+    // walked[i] = distance walking from the first item to the i-th item, in order.
+    let mut walked = vec![0.0; order.len()];
+    for i in 1..order.len() {
+        walked[i] = walked[i - 1] + point(i - 1).distance(&point(i));
+    }
+    // teleporting to the waypoint nearest to the item and walking to it.
+    let arrive = |position: usize| {
+        let to = point(position);
+        let waypoint = nearest(waypoints, to).unwrap();
+        teleport_cost + waypoints[waypoint].distance(&to)
+    };
+
+    // cost[i] = cheapest way to collect the first i items, leg_start[i] = where its last leg
+    // began. legs are found by running the leg with the cheapest `cost[j] + arrive(j) - walked[j]`
+    // so far up to i, since walking on from j to i adds `walked[i - 1] - walked[j]`.
+    let mut cost = vec![0.0; order.len() + 1];
+    let mut leg_start = vec![0; order.len() + 1];
+    let mut best = (f32::MAX, 0);
+    for i in 1..=order.len() {
+        let j = i - 1;
+        let candidate = cost[j] + arrive(j) - walked[j];
+        if candidate < best.0 {
+            best = (candidate, j);
+        }
+        cost[i] = best.0 + walked[i - 1];
+        leg_start[i] = best.1;
+    }
+
+    let mut legs = vec![];
+    let mut end = order.len();
+    while end > 0 {
+        let start = leg_start[end];
+        legs.push(LegSplit {
+            waypoint: nearest(waypoints, point(start)),
+            range: start..end,
+        });
+        end = start;
+    }
+    legs.reverse();
+    legs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_into_legs() {
+        // two clusters far apart, each with a waypoint next to it.
+        let items = [
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            Point::new(1000.0, 0.0),
+            Point::new(1010.0, 0.0),
+        ];
+        let waypoints = [Point::new(-5.0, 0.0), Point::new(995.0, 0.0)];
+
+        let legs = split_into_legs(&items, &[0, 1, 2, 3], &waypoints, 100.0);
+        assert_eq!(
+            legs,
+            vec![
+                LegSplit {
+                    waypoint: Some(0),
+                    range: 0..2
+                },
+                LegSplit {
+                    waypoint: Some(1),
+                    range: 2..4
+                },
+            ]
+        );
+
+        // teleporting is too expensive, walk everything in one leg.
+        let legs = split_into_legs(&items, &[0, 1, 2, 3], &waypoints, 10_000.0);
+        assert_eq!(legs.len(), 1);
+        assert_eq!(legs[0].range, 0..4);
+    }
+
+    #[test]
+    fn test_split_without_waypoints() {
+        let items = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let legs = split_into_legs(&items, &[1, 0], &[], 100.0);
+        assert_eq!(
+            legs,
+            vec![LegSplit {
+                waypoint: None,
+                range: 0..2
+            }]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+pub mod legs;
+pub mod tsp;
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::models::{Label, Marker};
+use crate::selection::MapSelection;
+use crate::shapes::point::Point;
+
+/// labels whose markers can be teleported to, so routes can start at them for free.
+pub const START_LABELS: [&str; 2] = ["Teleport Waypoint", "Statue of The Seven"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteOptions {
+    /// how many map units of walking a teleport is worth (loading screen, menus..).
+    /// lower values split the route into more legs.
+    pub teleport_cost: f32,
+}
+
+impl Default for RouteOptions {
+    fn default() -> Self {
+        Self {
+            teleport_cost: 400.0,
+        }
+    }
+}
+
+/// a marker on the route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteStop {
+    pub label: String,
+    /// position relative to the origin, same as the API.
+    pub pos: Point,
+}
+
+/// teleport to a waypoint and collect the stops from there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Leg {
+    /// waypoint or statue to teleport to, `None` if there's none in the frame.
+    pub start: Option<RouteStop>,
+    /// markers to collect, in order.
+    pub stops: Vec<RouteStop>,
+    /// walking distance in map units, from the start.
+    pub length: f32,
+}
+
+/// an ordered collection run, split into legs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedRoute {
+    pub legs: Vec<Leg>,
+}
+
+impl PlannedRoute {
+    /// total walking distance in map units.
+    pub fn length(&self) -> f32 {
+        self.legs.iter().map(|leg| leg.length).sum()
+    }
+
+    /// all the stops of all the legs, in order.
+    pub fn stops(&self) -> impl Iterator<Item = &RouteStop> {
+        self.legs.iter().flat_map(|leg| leg.stops.iter())
+    }
+
+    /// "teleport to X, collect N items" steps with numbered stops under each,
+    /// for printing next to the map.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut number = 1;
+        for (i, leg) in self.legs.iter().enumerate() {
+            let _ = match &leg.start {
+                Some(start) => write!(
+                    text,
+                    "leg {}: teleport to {} ({:.0}, {:.0}), ",
+                    i + 1,
+                    start.label,
+                    start.pos.x,
+                    start.pos.y
+                ),
+                None => write!(text, "leg {}: ", i + 1),
+            };
+            let _ = writeln!(
+                text,
+                "collect {} items ({:.0} units)",
+                leg.stops.len(),
+                leg.length
+            );
+            for stop in &leg.stops {
+                let _ = writeln!(
+                    text,
+                    "  {}. {} ({:.0}, {:.0})",
+                    number, stop.label, stop.pos.x, stop.pos.y
+                );
+                number += 1;
+            }
+        }
+        let teleports = self.legs.iter().filter(|leg| leg.start.is_some()).count();
+        let _ = writeln!(
+            text,
+            "total: {} teleports, {:.0} units walked",
+            teleports,
+            self.length()
+        );
+        text
+    }
+}
+
+fn is_start_label(name: &str) -> bool {
+    START_LABELS.iter().any(|start| name.contains(start))
+}
+
+fn to_stops(matched_markers: Vec<(&Label, Vec<&Marker>)>) -> Vec<RouteStop> {
+    matched_markers
+        .into_iter()
+        .flat_map(|(label, markers)| {
+            markers.into_iter().map(|marker| RouteStop {
+                label: label.name.clone(),
+                pos: marker.pos(),
+            })
+        })
+        .collect()
+}
+
+/// the matched markers (minus waypoints and statues) as stops, and the matched start
+/// markers (see `start_labels`) as free start points.
+pub fn collect_stops(
+    matched_markers: Vec<(&Label, Vec<&Marker>)>,
+    start_markers: Vec<(&Label, Vec<&Marker>)>,
+) -> (Vec<RouteStop>, Vec<RouteStop>) {
+    let items = to_stops(matched_markers)
+        .into_iter()
+        .filter(|stop| !is_start_label(&stop.label))
+        .collect();
+    let starts = to_stops(start_markers);
+
+    (items, starts)
+}
+
+/// `START_LABELS`, to match the start markers with.
+pub fn start_labels() -> Vec<String> {
+    START_LABELS.iter().map(|s| s.to_string()).collect()
+}
+
+/// plans a short collection run over the given items. the visiting order is found first
+/// (see `tsp`) and then split into legs that begin at the nearest start (see `legs`).
+pub fn plan(items: &[RouteStop], starts: &[RouteStop], options: &RouteOptions) -> PlannedRoute {
+    let item_points: Vec<Point> = items.iter().map(|stop| stop.pos).collect();
+    let start_points: Vec<Point> = starts.iter().map(|stop| stop.pos).collect();
+
+    let tour = tsp::plan_tour(&item_points, &start_points);
+    let splits = legs::split_into_legs(
+        &item_points,
+        &tour.order,
+        &start_points,
+        options.teleport_cost,
+    );
+
+    let legs = splits
+        .into_iter()
+        .map(|split| {
+            let order = &tour.order[split.range];
+            Leg {
+                start: split.waypoint.map(|i| starts[i].clone()),
+                stops: order.iter().map(|i| items[*i].clone()).collect(),
+                length: tsp::path_length(
+                    &item_points,
+                    order,
+                    split.waypoint.map(|i| start_points[i]),
+                ),
+            }
+        })
+        .collect();
+
+    PlannedRoute { legs }
+}
+
+/// plans a collection run over the desired markers of the selection.
+/// see `plan_route_over` to leave some of the markers out.
+pub fn plan_route(
+    selection: &MapSelection,
+    desired_marker_labels: &[String],
+    options: &RouteOptions,
+) -> PlannedRoute {
+    plan_route_over(
+        selection.matched_markers(desired_marker_labels),
+        selection.matched_markers(&start_labels()),
+        options,
+    )
+}
+
+/// plans a collection run over already matched markers, like the ones a mask leaves.
+pub fn plan_route_over(
+    matched_markers: Vec<(&Label, Vec<&Marker>)>,
+    start_markers: Vec<(&Label, Vec<&Marker>)>,
+    options: &RouteOptions,
+) -> PlannedRoute {
+    let (items, starts) = collect_stops(matched_markers, start_markers);
+    plan(&items, &starts, options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stop(label: &str, x: f32, y: f32) -> RouteStop {
+        RouteStop {
+            label: label.to_string(),
+            pos: Point::new(x, y),
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let items = [
+            stop("Qingxin", 1010.0, 0.0),
+            stop("Qingxin", 0.0, 0.0),
+            stop("Qingxin", 1000.0, 0.0),
+            stop("Qingxin", 10.0, 0.0),
+        ];
+        let starts = [
+            stop("Teleport Waypoint", -5.0, 0.0),
+            stop("Teleport Waypoint", 995.0, 0.0),
+        ];
+
+        let route = plan(&items, &starts, &RouteOptions::default());
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.legs[0].stops.len(), 2);
+        assert_eq!(route.length(), 30.0);
+    }
+
+    #[test]
+    fn test_to_text() {
+        let route = PlannedRoute {
+            legs: vec![Leg {
+                start: Some(stop("Teleport Waypoint", 0.0, 0.0)),
+                stops: vec![stop("Qingxin", 3.0, 4.0)],
+                length: 5.0,
+            }],
+        };
+
+        assert_eq!(
+            route.to_text(),
+            "leg 1: teleport to Teleport Waypoint (0, 0), collect 1 items (5 units)\n  \
+             1. Qingxin (3, 4)\n\
+             total: 1 teleports, 5 units walked\n"
+        );
+    }
+
+    #[test]
+    fn test_is_start_label() {
+        assert!(is_start_label("Teleport Waypoint"));
+        assert!(is_start_label("Statue of The Seven"));
+        assert!(!is_start_label("Qingxin"));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::shapes::point::Point;
+
+/// max number of 2-opt passes, each pass is O(n^2).
+const MAX_TWO_OPT_PASSES: usize = 50;
+
+/// a visiting order over a list of points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tour {
+    /// index of the start point the tour begins at, if any were given.
+    pub start: Option<usize>,
+    /// indices of the items, in visiting order.
+    pub order: Vec<usize>,
+    /// total walking distance in map units, including from the start to the first item.
+    pub length: f32,
+}
+
+/// finds a short path visiting every item once, beginning at one of the start points
+/// (waypoints, statues..) which are free to get to. the path doesn't return anywhere.
+///
+/// nearest neighbour from every start point, the best of those is improved with 2-opt.
+pub fn plan_tour(items: &[Point], starts: &[Point]) -> Tour {
+    if items.is_empty() {
+        return Tour {
+            start: None,
+            order: vec![],
+            length: 0.0,
+        };
+    }
+
+    let (start, mut order) = if starts.is_empty() {
+        // no start points, begin at an extremity of the items. a point far from
+        // the center is usually where a good path starts or ends.
+        let center = centroid(items);
+        let first = (0..items.len())
+            .max_by(|a, b| {
+                items[*a]
+                    .distance(&center)
+                    .total_cmp(&items[*b].distance(&center))
+            })
+            .unwrap_or(0);
+        (None, nearest_neighbour(items, items[first]))
+    } else {
+        (0..starts.len())
+            .map(|start| (Some(start), nearest_neighbour(items, starts[start])))
+            .min_by(|(a_start, a), (b_start, b)| {
+                let a = path_length(items, a, a_start.map(|i| starts[i]));
+                let b = path_length(items, b, b_start.map(|i| starts[i]));
+                a.total_cmp(&b)
+            })
+            .unwrap()
+    };
+
+    two_opt(items, &mut order, start.map(|i| starts[i]));
+
+    // after 2-opt, the path may be better walked the other way round, from another start.
+    let start = if starts.is_empty() {
+        None
+    } else {
+        let first = items[order[0]];
+        let last = items[*order.last().unwrap()];
+        let closest = |to: Point| {
+            (0..starts.len())
+                .min_by(|a, b| {
+                    starts[*a]
+                        .distance(&to)
+                        .total_cmp(&starts[*b].distance(&to))
+                })
+                .unwrap()
+        };
+        let (to_first, to_last) = (closest(first), closest(last));
+        if starts[to_last].distance(&last) < starts[to_first].distance(&first) {
+            order.reverse();
+            Some(to_last)
+        } else {
+            Some(to_first)
+        }
+    };
+
+    let length = path_length(items, &order, start.map(|i| starts[i]));
+    Tour {
+        start,
+        order,
+        length,
+    }
+}
+
+fn centroid(points: &[Point]) -> Point {
+    let (x, y) = points
+        .iter()
+        .fold((0.0, 0.0), |(x, y), point| (x + point.x, y + point.y));
+    Point::new(x / points.len() as f32, y / points.len() as f32)
+}
+
+/// greedily visits the closest unvisited item, beginning at `from`.
+fn nearest_neighbour(items: &[Point], from: Point) -> Vec<usize> {
+    let mut visited = vec![false; items.len()];
+    let mut order = Vec::with_capacity(items.len());
+    let mut current = from;
+
+    for _ in 0..items.len() {
+        let next = (0..items.len())
+            .filter(|i| !visited[*i])
+            .min_by(|a, b| {
+                items[*a]
+                    .distance(&current)
+                    .total_cmp(&items[*b].distance(&current))
+            })
+            .unwrap();
+        visited[next] = true;
+        order.push(next);
+        current = items[next];
+    }
+
+    order
+}
+
+/// total length of walking the items in order, from `start` if given.
+pub fn path_length(items: &[Point], order: &[usize], start: Option<Point>) -> f32 {
+    let walk: f32 = order
+        .windows(2)
+        .map(|pair| items[pair[0]].distance(&items[pair[1]]))
+        .sum();
+    let lead = match (start, order.first()) {
+        (Some(start), Some(first)) => start.distance(&items[*first]),
+        _ => 0.0,
+    };
+    lead + walk
+}
+
+/// reverses segments of the path as long as that makes it shorter.
+/// the path is open: it begins at `start` (if given) and ends wherever.
+fn two_opt(items: &[Point], order: &mut [usize], start: Option<Point>) {
+    let n = order.len();
+    if n < 2 {
+        return;
+    }
+    // the point before position i in the path.
+    let before = |order: &[usize], i: usize| -> Option<Point> {
+        if i == 0 {
+            start
+        } else {
+            Some(items[order[i - 1]])
+        }
+    };
+    let distance = |a: Option<Point>, b: Option<Point>| match (a, b) {
+        (Some(a), Some(b)) => a.distance(&b),
+        _ => 0.0,
+    };
+
+    for _ in 0..MAX_TWO_OPT_PASSES {
+        let mut improved = false;
+        for i in 0..n - 1 {
+            for k in i + 1..n {
+                let a = before(order, i);
+                let b = Some(items[order[i]]);
+                let c = Some(items[order[k]]);
+                let d = order.get(k + 1).map(|next| items[*next]);
+
+                // reversing order[i..=k] replaces edges a-b and c-d with a-c and b-d.
+                let delta = distance(a, c) + distance(b, d) - distance(a, b) - distance(c, d);
+                if delta < -1e-3 {
+                    order[i..=k].reverse();
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_plan_tour_line() {
+        // points on a line, shuffled. best path walks them in order from the start.
+        let items: Vec<Point> = [5.0, 1.0, 4.0, 2.0, 3.0]
+            .iter()
+            .map(|x| Point::new(*x, 0.0))
+            .collect();
+        let starts = [Point::new(100.0, 0.0), Point::new(0.0, 0.0)];
+
+        let tour = plan_tour(&items, &starts);
+        assert_eq!(tour.start, Some(1));
+        assert_eq!(tour.order, vec![1, 3, 4, 2, 0]);
+        assert_eq!(tour.length, 5.0);
+    }
+
+    #[test]
+    fn test_two_opt_uncrosses() {
+        // a square walked with crossing edges.
+        let items = [
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 10.0),
+            Point::new(10.0, 0.0),
+            Point::new(0.0, 10.0),
+        ];
+        let mut order = vec![0, 1, 2, 3];
+        let before = path_length(&items, &order, None);
+        two_opt(&items, &mut order, None);
+
+        assert!(path_length(&items, &order, None) < before);
+        assert_eq!(path_length(&items, &order, None), 30.0);
+    }
+
+    #[test]
+    fn test_plan_tour_empty() {
+        let tour = plan_tour(&[], &[Point::new(0.0, 0.0)]);
+        assert!(tour.order.is_empty());
+        assert_eq!(tour.start, None);
+    }
+}
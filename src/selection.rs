@@ -0,0 +1,64 @@
+use crate::api::models::{Label, MapData, Marker, MarkerData};
+use crate::render::Viewport;
+use crate::shapes::{point::Point, rect::Rect};
+use crate::spatial::MarkerIndex;
+
+/// a chunk of a map picked by region or area name, with all the data needed to render it.
+pub struct MapSelection {
+    /// name of the matched region or area.
+    pub name: String,
+    pub map_id: u8,
+    pub map_data: MapData,
+    pub marker_data: MarkerData,
+    /// frame of the chunk, from the top left of the map.
+    pub frame: Rect,
+    /// frames of the regions the selection is made of, from the top left of the map.
+    /// the frame is their bounding box, so it can cover bits of other regions too.
+    pub regions: Vec<Rect>,
+}
+
+impl MapSelection {
+    /// viewport mapping API co-ordinates to pixels of the rendered chunk.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::new(self.map_data.origin(), &self.frame)
+    }
+
+    /// whether the position (relative to the origin, like markers) is inside one of the
+    /// selected regions.
+    pub fn in_regions(&self, pos: Point) -> bool {
+        let pos = pos.abs_point(self.map_data.origin());
+        self.regions.iter().any(|region| {
+            (region.lx as f32..region.rx as f32).contains(&pos.x)
+                && (region.ly as f32..region.ry as f32).contains(&pos.y)
+        })
+    }
+
+    /// when the data was last changed, as the API sends it ("yyyy-mm-dd hh:mm:ss"): the
+    /// creation time of the newest marker. `None` if the markers have no times.
+    pub fn data_timestamp(&self) -> Option<String> {
+        self.marker_data
+            .markers
+            .iter()
+            .filter_map(|marker| marker.ctime.as_ref())
+            .filter(|ctime| !ctime.is_empty())
+            .max()
+            .cloned()
+    }
+
+    /// matched labels along with their markers that fall inside the frame.
+    pub fn matched_markers(&self, desired_marker_labels: &[String]) -> Vec<(&Label, Vec<&Marker>)> {
+        let index = MarkerIndex::new(&self.marker_data);
+        let origin = self.map_data.origin();
+
+        self.marker_data
+            .matching_labels(desired_marker_labels)
+            .into_iter()
+            .map(|label| {
+                (
+                    label,
+                    index.within_rect(&self.frame, origin, Some(label.id)),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,621 @@
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use reqwest::Url;
+use tokio::runtime::Handle;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::Mutex;
+
+use crate::export::raster::{self, RasterFormat, RasterMetadata};
+use crate::export::tiles::{TileOptions, TileSource};
+use crate::progress::ProgressEvents;
+use crate::MapGenerator;
+
+/// what to render for `/render`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderFormat {
+    /// `png`, `jpeg:80` etc, see `RasterFormat`
+    Raster(RasterFormat),
+    Svg,
+    Html,
+}
+
+impl RenderFormat {
+    fn parse(format: &str) -> Option<Self> {
+        match format {
+            "svg" => Some(Self::Svg),
+            "html" => Some(Self::Html),
+            _ => RasterFormat::parse(format).map(Self::Raster),
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Raster(format) => format.content_type(),
+            Self::Svg => "image/svg+xml",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// a region (or sub region) or an area to render, same as `gen_region_map`/`gen_area_map`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Region(String),
+    Area(String),
+}
+
+/// the endpoints of the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    /// `/render?region=Sumeru&labels=Teleport Waypoint,Padisarah&format=png`
+    /// (`area=` instead of `region=` for a whole area, `format` is png by default,
+    /// or `jpeg:80`, `svg`, `html` etc, see `RenderFormat`)
+    Render {
+        target: Target,
+        labels: Vec<String>,
+        format: RenderFormat,
+    },
+    /// `/tiles/{map}/{z}/{x}/{y}.png?labels=...` in the XYZ scheme, see `export::tiles`
+    Tile {
+        map_id: u8,
+        zoom: u32,
+        x: u32,
+        y: u32,
+        labels: Vec<String>,
+    },
+    /// `/api/markers?map=2&labels=...&region=...` as JSON, see `export::table`
+    /// (`region` optionally keeps only the markers in matching regions or sub regions)
+    Markers {
+        map_id: u8,
+        labels: Vec<String>,
+        region: Option<String>,
+    },
+    /// `/events`, the progress of the renders as server-sent events, JSON encoded
+    /// `Progress` in the `data` of each event.
+    Events,
+}
+
+/// parses the path and query of a request, `None` if it isn't one of the endpoints.
+pub fn parse_route(path_and_query: &str) -> Option<Route> {
+    let url = Url::parse(&format!("http://localhost{path_and_query}")).ok()?;
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.into_owned())
+    };
+    // comma separated, empty for none.
+    let labels: Vec<String> = query("labels")
+        .map(|labels| {
+            labels
+                .split(',')
+                .map(str::trim)
+                .filter(|label| !label.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    match segments[..] {
+        ["render"] => {
+            let target = match (query("region"), query("area")) {
+                (Some(region), None) => Target::Region(region),
+                (None, Some(area)) => Target::Area(area),
+                _ => return None,
+            };
+            let format = match query("format") {
+                Some(format) => RenderFormat::parse(&format)?,
+                None => RenderFormat::Raster(RasterFormat::Png),
+            };
+            Some(Route::Render {
+                target,
+                labels,
+                format,
+            })
+        }
+        ["tiles", map_id, zoom, x, y] => Some(Route::Tile {
+            map_id: map_id.parse().ok()?,
+            zoom: zoom.parse().ok()?,
+            x: x.parse().ok()?,
+            y: y.strip_suffix(".png")?.parse().ok()?,
+            labels,
+        }),
+        ["api", "markers"] => Some(Route::Markers {
+            map_id: query("map")?.parse().ok()?,
+            labels,
+            region: query("region"),
+        }),
+        ["events"] => Some(Route::Events),
+        _ => None,
+    }
+}
+
+/// how many label sets get their tiles cached, the least recently used one is deleted to
+/// make room for a new one.
+const MAX_TILE_SETS: usize = 16;
+
+/// directory the tiles of the map with the labels are cached in, named by a hash of the
+/// sorted labels so that every set of labels gets its own.
+fn tiles_dir(root: &Path, map_id: u8, labels: &[String]) -> PathBuf {
+    let mut labels = labels.to_vec();
+    labels.sort();
+    labels.dedup();
+    // FNV-1a, stable across builds so that the cache survives restarts.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in labels.join("\0").bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    root.join(map_id.to_string()).join(format!("{hash:016x}"))
+}
+
+/// the label sets with cached tiles, least recently used first.
+#[derive(Default)]
+struct TileSets {
+    sets: Vec<TileSet>,
+}
+
+struct TileSet {
+    dir: PathBuf,
+    /// `None` until a tile has to be rendered, for sets cached by an earlier run.
+    source: Option<Arc<TileSource>>,
+}
+
+impl TileSets {
+    /// the sets cached under the root by earlier runs, oldest first.
+    fn scan(root: &Path) -> Self {
+        let mut dirs = vec![];
+        for map_dir in fs::read_dir(root).into_iter().flatten().flatten() {
+            for set_dir in fs::read_dir(map_dir.path()).into_iter().flatten().flatten() {
+                let modified = set_dir.metadata().and_then(|metadata| metadata.modified());
+                if let Ok(modified) = modified {
+                    dirs.push((modified, set_dir.path()));
+                }
+            }
+        }
+        dirs.sort();
+        Self {
+            sets: dirs
+                .into_iter()
+                .map(|(_, dir)| TileSet { dir, source: None })
+                .collect(),
+        }
+    }
+
+    /// marks the set as just used, `None` if it isn't cached.
+    fn touch(&mut self, dir: &Path) -> Option<&mut TileSet> {
+        let i = self.sets.iter().position(|set| set.dir == dir)?;
+        let set = self.sets.remove(i);
+        self.sets.push(set);
+        self.sets.last_mut()
+    }
+
+    /// adds (or updates) the set as the most recently used, returns the directories of the
+    /// sets that have to go to make room for it.
+    fn insert(&mut self, dir: PathBuf, source: Arc<TileSource>) -> Vec<PathBuf> {
+        match self.touch(&dir) {
+            Some(set) => set.source = Some(source),
+            None => self.sets.push(TileSet {
+                dir,
+                source: Some(source),
+            }),
+        }
+        let evicted = self.sets.len().saturating_sub(MAX_TILE_SETS);
+        self.sets.drain(..evicted).map(|set| set.dir).collect()
+    }
+}
+
+struct Server {
+    generator: MapGenerator,
+    tiles_root: PathBuf,
+    /// tiles are rendered on demand, and cached on disk per label set.
+    tile_sets: Mutex<TileSets>,
+    events: ProgressEvents,
+}
+
+fn response(status: StatusCode, content_type: &str, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", content_type)
+        .body(body.into())
+        .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    response(StatusCode::NOT_FOUND, "text/plain", "not found")
+}
+
+impl Server {
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET {
+            return response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "text/plain",
+                "only GET is supported",
+            );
+        }
+        let path_and_query = request
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let Some(route) = parse_route(path_and_query) else {
+            return not_found();
+        };
+
+        match self.respond(route).await {
+            Ok(Some(response)) => response,
+            Ok(None) => not_found(),
+            Err(e) => response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "text/plain",
+                format!("{e:#}"),
+            ),
+        }
+    }
+
+    /// the source of the tiles of the label set, made the first time it's needed.
+    /// sets over `MAX_TILE_SETS` are deleted, least recently used first.
+    async fn tile_source(
+        &self,
+        map_id: u8,
+        labels: &[String],
+        dir: &Path,
+    ) -> anyhow::Result<Arc<TileSource>> {
+        if let Some(source) = self
+            .tile_sets
+            .lock()
+            .await
+            .touch(dir)
+            .and_then(|set| set.source.clone())
+        {
+            return Ok(source);
+        }
+
+        // not holding the lock, so that other sets aren't held up while this one is fetched.
+        let source = Arc::new(
+            self.generator
+                .tile_source(map_id, labels, &TileOptions::default())
+                .await?,
+        );
+        let evicted = self
+            .tile_sets
+            .lock()
+            .await
+            .insert(dir.to_path_buf(), source.clone());
+        for dir in evicted {
+            tracing::debug!(dir = %dir.display(), "evicting cached tiles");
+            if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(dir = %dir.display(), error = %e, "could not delete cached tiles");
+                }
+            }
+        }
+        Ok(source)
+    }
+
+    /// `None` when there's nothing matching the request.
+    async fn respond(&self, route: Route) -> anyhow::Result<Option<Response<Body>>> {
+        match route {
+            Route::Render {
+                target,
+                labels,
+                format,
+            } => {
+                let generator = self.generator.clone();
+                // rendering and encoding are CPU heavy, so they run on a blocking thread
+                // instead of holding up the other requests on the async workers.
+                let body = tokio::task::spawn_blocking(move || {
+                    Handle::current().block_on(render_body(&generator, target, labels, format))
+                })
+                .await??;
+                let Some(body) = body else {
+                    return Ok(None);
+                };
+                Ok(Some(response(StatusCode::OK, format.content_type(), body)))
+            }
+            Route::Tile {
+                map_id,
+                zoom,
+                x,
+                y,
+                labels,
+            } => {
+                let dir = tiles_dir(&self.tiles_root, map_id, &labels);
+                let path = dir
+                    .join(zoom.to_string())
+                    .join(x.to_string())
+                    .join(format!("{y}.png"));
+                let cached = self.tile_sets.lock().await.touch(&dir).is_some();
+                if cached {
+                    match tokio::fs::read(&path).await {
+                        Ok(bytes) => return Ok(Some(response(StatusCode::OK, "image/png", bytes))),
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                let source = self.tile_source(map_id, &labels, &dir).await?;
+                let generator = self.generator.clone();
+                // same as renders, off the async workers.
+                let tile = tokio::task::spawn_blocking(move || {
+                    let Some(tile) =
+                        Handle::current().block_on(generator.render_tile(&source, zoom, x, y))?
+                    else {
+                        return Ok(None);
+                    };
+                    let mut bytes = vec![];
+                    raster::encode(&tile, RasterFormat::Png, None, &mut bytes)?;
+                    anyhow::Ok(Some(bytes))
+                })
+                .await??;
+                let Some(bytes) = tile else {
+                    return Ok(None);
+                };
+
+                // unless the set was evicted while the tile was rendered.
+                if self.tile_sets.lock().await.touch(&dir).is_some() {
+                    if let Err(e) = write_tile(&path, &bytes).await {
+                        tracing::warn!(path = %path.display(), error = %e, "could not cache the tile");
+                    }
+                }
+                Ok(Some(response(StatusCode::OK, "image/png", bytes)))
+            }
+            Route::Markers {
+                map_id,
+                labels,
+                region,
+            } => {
+                let mut rows = self.generator.gen_marker_rows(map_id, &labels).await?;
+                if let Some(region) = region {
+                    rows.retain(|row| {
+                        [&row.region, &row.sub_region]
+                            .into_iter()
+                            .flatten()
+                            .any(|name| name.contains(&region))
+                    });
+                }
+                Ok(Some(response(
+                    StatusCode::OK,
+                    "application/json",
+                    serde_json::to_vec(&rows)?,
+                )))
+            }
+            Route::Events => {
+                let mut receiver = self.events.subscribe();
+                let (mut sender, body) = Body::channel();
+                tokio::spawn(async move {
+                    // a comment, so that the response starts before the first event.
+                    if sender.send_data(": listening\n\n".into()).await.is_err() {
+                        return;
+                    }
+                    loop {
+                        let progress = match receiver.recv().await {
+                            Ok(progress) => progress,
+                            // skip what this listener was too slow for.
+                            Err(RecvError::Lagged(_)) => continue,
+                            Err(RecvError::Closed) => break,
+                        };
+                        let Ok(json) = serde_json::to_string(&progress) else {
+                            continue;
+                        };
+                        // the client went away.
+                        if sender
+                            .send_data(format!("data: {json}\n\n").into())
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+                Ok(Some(response(StatusCode::OK, "text/event-stream", body)))
+            }
+        }
+    }
+}
+
+/// the body of a `/render`, `None` if nothing matched.
+async fn render_body(
+    generator: &MapGenerator,
+    target: Target,
+    labels: Vec<String>,
+    format: RenderFormat,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let body = match format {
+        RenderFormat::Raster(raster_format) => {
+            let selection = match target {
+                Target::Region(name) => generator.select_region(&name).await?,
+                Target::Area(name) => generator.select_area(&name).await?,
+            };
+            let Some(selection) = selection else {
+                return Ok(None);
+            };
+            let image = generator.render(&selection, &labels).await?;
+            let metadata = RasterMetadata::for_selection(&selection, &labels);
+
+            let mut bytes = vec![];
+            raster::encode(&image, raster_format, Some(&metadata), &mut bytes)?;
+            bytes
+        }
+        RenderFormat::Svg => {
+            let svg = match target {
+                Target::Region(name) => generator.gen_region_svg(&name, labels).await?,
+                Target::Area(name) => generator.gen_area_svg(&name, labels).await?,
+            };
+            let Some(svg) = svg else {
+                return Ok(None);
+            };
+            svg.into_bytes()
+        }
+        RenderFormat::Html => {
+            let html = match target {
+                Target::Region(name) => generator.gen_region_html(&name, labels).await?,
+                Target::Area(name) => generator.gen_area_html(&name, labels).await?,
+            };
+            let Some(html) = html else {
+                return Ok(None);
+            };
+            html.into_bytes()
+        }
+    };
+    Ok(Some(body))
+}
+
+/// writes the tile through a temporary file, so that a tile is never read half written.
+async fn write_tile(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let temporary = path.with_extension("png.tmp");
+    tokio::fs::write(&temporary, bytes).await?;
+    tokio::fs::rename(&temporary, path).await
+}
+
+/// serves rendered maps, tiles and markers over HTTP until the process is stopped.
+/// see `Route` for the endpoints. tiles are rendered the first time they're requested and
+/// cached under `tiles_root`, for up to `MAX_TILE_SETS` sets of labels.
+/// `/events` streams what `events` gets, so give its hook to the generator.
+pub async fn serve(
+    generator: MapGenerator,
+    addr: SocketAddr,
+    tiles_root: impl Into<PathBuf>,
+    events: ProgressEvents,
+) -> anyhow::Result<()> {
+    let tiles_root = tiles_root.into();
+    let server = Arc::new(Server {
+        generator,
+        tile_sets: Mutex::new(TileSets::scan(&tiles_root)),
+        tiles_root,
+        events,
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let server = server.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let server = server.clone();
+                async move { Ok::<_, Infallible>(server.handle(request).await) }
+            }))
+        }
+    });
+
+    hyper::Server::try_bind(&addr)?.serve(make_service).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tiles::TileLayout;
+
+    #[test]
+    fn test_parse_route() {
+        assert_eq!(
+            parse_route("/render?region=Sumeru&labels=Teleport%20Waypoint,Padisarah&format=svg"),
+            Some(Route::Render {
+                target: Target::Region("Sumeru".to_string()),
+                labels: vec!["Teleport Waypoint".to_string(), "Padisarah".to_string()],
+                format: RenderFormat::Svg,
+            })
+        );
+        assert_eq!(
+            parse_route("/tiles/2/3/4/5.png"),
+            Some(Route::Tile {
+                map_id: 2,
+                zoom: 3,
+                x: 4,
+                y: 5,
+                labels: vec![],
+            })
+        );
+        assert_eq!(
+            parse_route("/api/markers?map=2&labels=Chest"),
+            Some(Route::Markers {
+                map_id: 2,
+                labels: vec!["Chest".to_string()],
+                region: None,
+            })
+        );
+        assert_eq!(
+            parse_route("/render?area=Inazuma&format=jpeg:80"),
+            Some(Route::Render {
+                target: Target::Area("Inazuma".to_string()),
+                labels: vec![],
+                format: RenderFormat::Raster(RasterFormat::Jpeg { quality: 80 }),
+            })
+        );
+        assert_eq!(parse_route("/events"), Some(Route::Events));
+    }
+
+    #[test]
+    fn test_parse_bad_route() {
+        assert_eq!(parse_route("/render?labels=Chest"), None);
+        assert_eq!(parse_route("/render?region=Sumeru&format=gif"), None);
+        assert_eq!(parse_route("/tiles/2/3/4/5.jpg"), None);
+        assert_eq!(parse_route("/api/markers"), None);
+        assert_eq!(parse_route("/"), None);
+        assert_eq!(parse_route("/events/2"), None);
+    }
+
+    #[test]
+    fn test_tiles_dir() {
+        let labels = |labels: &[&str]| -> Vec<String> {
+            labels.iter().map(|label| label.to_string()).collect()
+        };
+        let dir = |l: &[&str]| tiles_dir(Path::new("tiles"), 2, &labels(l));
+
+        assert!(dir(&["Chest"]).starts_with("tiles/2"));
+        assert_eq!(
+            dir(&["Teleport Waypoint", "Chest"]),
+            dir(&["Chest", "Teleport Waypoint"])
+        );
+        assert_ne!(dir(&["A B"]), dir(&["A_B"]));
+        assert_ne!(dir(&["A,B"]), dir(&["A", "B"]));
+        assert_ne!(dir(&[]), dir(&["Chest"]));
+        assert_ne!(
+            dir(&["Chest"]),
+            tiles_dir(Path::new("tiles"), 3, &labels(&["Chest"]))
+        );
+    }
+
+    #[test]
+    fn test_tile_sets() {
+        let source = || {
+            Arc::new(TileSource {
+                map_id: 2,
+                map_data: serde_json::from_value(serde_json::json!({
+                    "slices": [],
+                    "origin": [0.0, 0.0],
+                    "total_size": [0, 0],
+                    "padding": [0.0, 0.0],
+                }))
+                .unwrap(),
+                layout: TileLayout {
+                    tile_size: 256,
+                    slice_size: (256, 256),
+                    map_size: (0, 0),
+                    max_zoom: 0,
+                },
+                markers: vec![],
+                labels: vec![],
+            })
+        };
+        let dir = |i: usize| PathBuf::from(format!("tiles/2/{i}"));
+
+        let mut tile_sets = TileSets::default();
+        for i in 0..MAX_TILE_SETS {
+            assert!(tile_sets.insert(dir(i), source()).is_empty());
+        }
+        // the first one is used again, so the second one goes.
+        assert!(tile_sets.touch(&dir(0)).is_some());
+        assert_eq!(tile_sets.insert(dir(MAX_TILE_SETS), source()), vec![dir(1)]);
+        assert!(tile_sets.touch(&dir(1)).is_none());
+        assert!(tile_sets.touch(&dir(0)).unwrap().source.is_some());
+    }
+}
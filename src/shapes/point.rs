@@ -1,5 +1,7 @@
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Point {
     pub x: f32, 
     pub y: f32,
@@ -21,6 +23,11 @@ impl Point {
         }
     }
 
+    /// straight line distance between the two points.
+    pub fn distance(&self, other: &Point) -> f32 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2)).sqrt()
+    }
+
     pub fn translate_axes(&self, new_origin: Point) -> Self {
         Self {
             x: self.x - new_origin.x,
@@ -6,7 +6,7 @@ use crate::shapes::point::Point;
 /// and also easy to implement some logic.
 /// TODO: add validation to match the above pattern
 /// or calculate those values from given two points.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Rect {
     pub lx: u32,
     pub ly: u32,
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use crate::api::models::{Marker, MarkerData, RegionData};
+use crate::shapes::{point::Point, rect::Rect};
+
+/// default size of a grid cell in map units.
+pub const DEFAULT_CELL_SIZE: f32 = 256.0;
+
+/// uniform grid over the markers, for spatial queries without scanning every marker.
+/// positions are relative to the map's origin, same as `Marker::pos`.
+pub struct MarkerIndex<'a> {
+    markers: &'a [Marker],
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    by_label: HashMap<i32, Vec<usize>>,
+    /// min and max cell co-ordinates that have markers in them.
+    extent: Option<((i32, i32), (i32, i32))>,
+}
+
+impl<'a> MarkerIndex<'a> {
+    pub fn new(marker_data: &'a MarkerData) -> Self {
+        Self::with_cell_size(&marker_data.markers, DEFAULT_CELL_SIZE)
+    }
+
+    pub fn with_cell_size(markers: &'a [Marker], cell_size: f32) -> Self {
+        let mut index = Self {
+            markers,
+            cell_size: cell_size.max(1.0),
+            cells: HashMap::new(),
+            by_label: HashMap::new(),
+            extent: None,
+        };
+
+        for (i, marker) in markers.iter().enumerate() {
+            let cell = index.cell(marker.pos());
+            index.cells.entry(cell).or_default().push(i);
+            index.by_label.entry(marker.label_id).or_default().push(i);
+            index.extent = Some(match index.extent {
+                None => (cell, cell),
+                Some((min, max)) => (
+                    (min.0.min(cell.0), min.1.min(cell.1)),
+                    (max.0.max(cell.0), max.1.max(cell.1)),
+                ),
+            });
+        }
+
+        index
+    }
+
+    fn cell(&self, pos: Point) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// all the markers of the label.
+    pub fn with_label(&self, label_id: i32) -> impl Iterator<Item = &'a Marker> + '_ {
+        self.by_label
+            .get(&label_id)
+            .into_iter()
+            .flatten()
+            .map(|i| &self.markers[*i])
+    }
+
+    /// markers in the cells overlapping the bounds (not filtered by exact position), with
+    /// their positions in the markers the index was made from.
+    fn candidates(
+        &self,
+        top_left: Point,
+        bottom_right: Point,
+        label_id: Option<i32>,
+    ) -> impl Iterator<Item = (usize, &'a Marker)> + '_ {
+        let (min, max) = (self.cell(top_left), self.cell(bottom_right));
+        // clamp to where markers are, so huge bounds don't loop over empty cells.
+        let (min, max) = match self.extent {
+            Some((lo, hi)) => (
+                (min.0.max(lo.0), min.1.max(lo.1)),
+                (max.0.min(hi.0), max.1.min(hi.1)),
+            ),
+            None => ((0, 0), (-1, -1)),
+        };
+
+        (min.1..=max.1)
+            .flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|i| (*i, &self.markers[*i]))
+            .filter(move |(_, marker)| label_id.is_none_or(|id| marker.label_id == id))
+    }
+
+    /// markers with `top_left <= pos < bottom_right`.
+    pub fn within_bounds(
+        &self,
+        top_left: Point,
+        bottom_right: Point,
+        label_id: Option<i32>,
+    ) -> Vec<&'a Marker> {
+        self.candidates(top_left, bottom_right, label_id)
+            .map(|(_, marker)| marker)
+            .filter(|marker| {
+                let pos = marker.pos();
+                pos.x >= top_left.x
+                    && pos.y >= top_left.y
+                    && pos.x < bottom_right.x
+                    && pos.y < bottom_right.y
+            })
+            .collect()
+    }
+
+    /// markers inside the frame of the region.
+    pub fn within_region(&self, region: &RegionData, label_id: Option<i32>) -> Vec<&'a Marker> {
+        self.within_bounds(
+            Point::new(region.lx, region.ly),
+            Point::new(region.rx, region.ry),
+            label_id,
+        )
+    }
+
+    /// markers inside the rect, which is from the top left of the map like all rects.
+    pub fn within_rect(
+        &self,
+        rect: &Rect,
+        origin: Point,
+        label_id: Option<i32>,
+    ) -> Vec<&'a Marker> {
+        self.within_bounds(
+            Point::new(rect.lx as f32 - origin.x, rect.ly as f32 - origin.y),
+            Point::new(rect.rx as f32 - origin.x, rect.ry as f32 - origin.y),
+            label_id,
+        )
+    }
+
+    /// markers at most `radius` away from the point.
+    pub fn within_radius(&self, pos: Point, radius: f32, label_id: Option<i32>) -> Vec<&'a Marker> {
+        self.positions_within_radius(pos, radius, label_id)
+            .into_iter()
+            .map(|i| &self.markers[i])
+            .collect()
+    }
+
+    /// same as `within_radius`, but the positions of the markers in the markers the index
+    /// was made from, for callers that keep state per marker.
+    pub fn positions_within_radius(
+        &self,
+        pos: Point,
+        radius: f32,
+        label_id: Option<i32>,
+    ) -> Vec<usize> {
+        let (top_left, bottom_right) = (
+            Point::new(pos.x - radius, pos.y - radius),
+            Point::new(pos.x + radius, pos.y + radius),
+        );
+        self.candidates(top_left, bottom_right, label_id)
+            .filter(|(_, marker)| marker.pos().distance(&pos) <= radius)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// the `n` markers closest to the point, closest first, with their distances.
+    ///
+    /// searches rings of cells around the point, and stops once the nth closest marker
+    /// found so far is closer than anything in the rings not searched yet.
+    pub fn nearest(&self, pos: Point, n: usize, label_id: Option<i32>) -> Vec<(&'a Marker, f32)> {
+        let Some((lo, hi)) = self.extent else {
+            return vec![];
+        };
+        if n == 0 {
+            return vec![];
+        }
+
+        let center = self.cell(pos);
+        let (center, lo, hi) = (
+            (center.0 as i64, center.1 as i64),
+            (lo.0 as i64, lo.1 as i64),
+            (hi.0 as i64, hi.1 as i64),
+        );
+        // the rings closer than the extent are empty, and the ones past it too, so only
+        // the rings from the first that touches the extent to the last one are walked.
+        let first_ring = [
+            lo.0 - center.0,
+            center.0 - hi.0,
+            lo.1 - center.1,
+            center.1 - hi.1,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0)
+        .max(0);
+        let max_ring = [
+            center.0 - lo.0,
+            hi.0 - center.0,
+            center.1 - lo.1,
+            hi.1 - center.1,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0)
+        .max(0);
+
+        let mut found: Vec<(&'a Marker, f32)> = vec![];
+        for ring in first_ring..=max_ring {
+            for cell in ring_cells(center, ring, (lo, hi)) {
+                let Some(indices) = self.cells.get(&cell) else {
+                    continue;
+                };
+                found.extend(
+                    indices
+                        .iter()
+                        .map(|i| &self.markers[*i])
+                        .filter(|marker| label_id.is_none_or(|id| marker.label_id == id))
+                        .map(|marker| (marker, marker.pos().distance(&pos))),
+                );
+            }
+
+            found.sort_by(|a, b| a.1.total_cmp(&b.1));
+            found.truncate(n);
+            // anything in the next ring is at least this far away.
+            let searched = ring as f32 * self.cell_size;
+            if found.len() == n && found[n - 1].1 <= searched {
+                break;
+            }
+        }
+
+        found
+    }
+}
+
+/// cells on the border of the square `ring` cells away from the center, that are inside
+/// the extent (min and max cells, inclusive).
+fn ring_cells(
+    center: (i64, i64),
+    ring: i64,
+    (lo, hi): ((i64, i64), (i64, i64)),
+) -> Vec<(i32, i32)> {
+    let inside = |x: i64, y: i64| (lo.0..=hi.0).contains(&x) && (lo.1..=hi.1).contains(&y);
+    if ring == 0 {
+        return if inside(center.0, center.1) {
+            vec![(center.0 as i32, center.1 as i32)]
+        } else {
+            vec![]
+        };
+    }
+
+    let mut cells = vec![];
+    // top and bottom rows, clamped to the extent.
+    let (left, right) = ((center.0 - ring).max(lo.0), (center.0 + ring).min(hi.0));
+    for y in [center.1 - ring, center.1 + ring] {
+        if (lo.1..=hi.1).contains(&y) {
+            cells.extend((left..=right).map(|x| (x as i32, y as i32)));
+        }
+    }
+    // left and right columns, without the corners.
+    let (top, bottom) = (
+        (center.1 - ring + 1).max(lo.1),
+        (center.1 + ring - 1).min(hi.1),
+    );
+    for x in [center.0 - ring, center.0 + ring] {
+        if (lo.0..=hi.0).contains(&x) {
+            cells.extend((top..=bottom).map(|y| (x as i32, y as i32)));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_markers() -> Vec<Marker> {
+        serde_json::from_str(
+            r#"[{"label_id": 1, "area_id": 1, "x_pos": 0.0, "y_pos": 0.0},
+                {"label_id": 1, "area_id": 1, "x_pos": 300.0, "y_pos": 0.0},
+                {"label_id": 2, "area_id": 1, "x_pos": 10.0, "y_pos": 10.0},
+                {"label_id": 1, "area_id": 1, "x_pos": -2000.0, "y_pos": 900.0}]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_nearest() {
+        let markers = test_markers();
+        let index = MarkerIndex::with_cell_size(&markers, 100.0);
+
+        let nearest = index.nearest(Point::new(250.0, 0.0), 2, Some(1));
+        let positions: Vec<Point> = nearest.iter().map(|(marker, _)| marker.pos()).collect();
+        assert_eq!(
+            positions,
+            vec![Point::new(300.0, 0.0), Point::new(0.0, 0.0)]
+        );
+
+        // far away markers are still found.
+        let nearest = index.nearest(Point::new(-1900.0, 900.0), 1, None);
+        assert_eq!(nearest[0].1, 100.0);
+    }
+
+    #[test]
+    fn test_within_radius() {
+        let markers = test_markers();
+        let index = MarkerIndex::with_cell_size(&markers, 100.0);
+
+        assert_eq!(
+            index.within_radius(Point::new(0.0, 0.0), 20.0, None).len(),
+            2
+        );
+        assert_eq!(
+            index
+                .within_radius(Point::new(0.0, 0.0), 20.0, Some(2))
+                .len(),
+            1
+        );
+        assert_eq!(
+            index.positions_within_radius(Point::new(300.0, 10.0), 20.0, None),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_within_bounds() {
+        let markers = test_markers();
+        let index = MarkerIndex::with_cell_size(&markers, 100.0);
+
+        let inside = index.within_bounds(Point::new(0.0, 0.0), Point::new(300.0, 300.0), None);
+        assert_eq!(inside.len(), 2);
+        assert_eq!(index.with_label(1).count(), 3);
+    }
+
+    #[test]
+    fn test_nearest_far_outside() {
+        let markers = test_markers();
+        let index = MarkerIndex::with_cell_size(&markers, 100.0);
+
+        let nearest = index.nearest(Point::new(1.0e9, -1.0e9), 1, None);
+        assert_eq!(nearest[0].0.pos(), Point::new(300.0, 0.0));
+
+        let nearest = index.nearest(Point::new(-1.0e12, 1.0e12), 2, Some(1));
+        assert_eq!(nearest[0].0.pos(), Point::new(-2000.0, 900.0));
+        assert_eq!(nearest.len(), 2);
+    }
+
+    #[test]
+    fn test_ring_cells() {
+        let extent = ((-10, -10), (10, 10));
+        assert_eq!(ring_cells((0, 0), 0, extent), vec![(0, 0)]);
+        assert_eq!(ring_cells((0, 0), 1, extent).len(), 8);
+        assert_eq!(ring_cells((0, 0), 2, extent).len(), 16);
+        // only the cells inside the extent.
+        assert_eq!(ring_cells((10, 0), 1, extent).len(), 5);
+        assert_eq!(ring_cells((100, 0), 90, extent).len(), 21);
+        assert!(ring_cells((100, 0), 89, extent).is_empty());
+        assert!(ring_cells((100, 0), 0, extent).is_empty());
+    }
+}